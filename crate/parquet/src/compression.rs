@@ -70,8 +70,12 @@ pub fn create_codec(codec: CodecType) -> Result<Option<Box<dyn Codec>>> {
         CodecType::SNAPPY => Ok(Some(Box::new(SnappyCodec::new()))),
         #[cfg(any(feature = "lz4", test))]
         CodecType::LZ4 => Ok(Some(Box::new(LZ4Codec::new()))),
+        #[cfg(all(feature = "lz4_flex", not(any(feature = "lz4", test))))]
+        CodecType::LZ4 => Ok(Some(Box::new(LZ4FlexCodec::new()))),
         #[cfg(any(feature = "zstd", test))]
         CodecType::ZSTD => Ok(Some(Box::new(ZSTDCodec::new()))),
+        #[cfg(all(feature = "ruzstd", not(any(feature = "zstd", test))))]
+        CodecType::ZSTD => Ok(Some(Box::new(RuZstdCodec::new()))),
         CodecType::UNCOMPRESSED => Ok(None),
         _ => Err(nyi_err!("The codec type {} is not supported yet", codec)),
     }
@@ -319,6 +323,92 @@ mod zstd_codec {
 #[cfg(any(feature = "zstd", test))]
 pub use zstd_codec::*;
 
+#[cfg(feature = "lz4_flex")]
+mod lz4_flex_codec {
+    use std::io::{self, Write};
+
+    use crate::compression::Codec;
+    use crate::errors::{ParquetError, Result};
+
+    /// Codec for LZ4 compression algorithm, pure Rust implementation which also
+    /// compiles to wasm32. Uses the LZ4 frame format like `LZ4Codec`.
+    pub struct LZ4FlexCodec {}
+
+    impl LZ4FlexCodec {
+        /// Creates new LZ4 compression codec.
+        pub(crate) fn new() -> Self {
+            Self {}
+        }
+    }
+
+    impl Codec for LZ4FlexCodec {
+        fn decompress(
+            &mut self,
+            input_buf: &[u8],
+            output_buf: &mut Vec<u8>,
+        ) -> Result<usize> {
+            let mut decoder = lz4_flex::frame::FrameDecoder::new(input_buf);
+            match io::copy(&mut decoder, output_buf) {
+                Ok(n) => Ok(n as usize),
+                Err(e) => Err(e.into()),
+            }
+        }
+
+        fn compress(&mut self, input_buf: &[u8], output_buf: &mut Vec<u8>) -> Result<()> {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(output_buf);
+            encoder.write_all(input_buf)?;
+            match encoder.finish() {
+                Ok(_) => Ok(()),
+                Err(e) => Err(general_err!("LZ4 compression failed: {}", e)),
+            }
+        }
+    }
+}
+#[cfg(feature = "lz4_flex")]
+pub use lz4_flex_codec::*;
+
+#[cfg(feature = "ruzstd")]
+mod ruzstd_codec {
+    use std::io;
+
+    use crate::compression::Codec;
+    use crate::errors::{ParquetError, Result};
+
+    /// Codec for Zstandard compression algorithm, pure Rust implementation which also
+    /// compiles to wasm32. Only supports decompression.
+    pub struct RuZstdCodec {}
+
+    impl RuZstdCodec {
+        /// Creates new Zstandard decompression codec.
+        pub(crate) fn new() -> Self {
+            Self {}
+        }
+    }
+
+    impl Codec for RuZstdCodec {
+        fn decompress(
+            &mut self,
+            mut input_buf: &[u8],
+            output_buf: &mut Vec<u8>,
+        ) -> Result<usize> {
+            let mut total_len = 0;
+            // A page may consist of several frames
+            while !input_buf.is_empty() {
+                let mut decoder = ruzstd::StreamingDecoder::new(&mut input_buf)
+                    .map_err(|e| general_err!("Zstandard decompression failed: {}", e))?;
+                total_len += io::copy(&mut decoder, output_buf)? as usize;
+            }
+            Ok(total_len)
+        }
+
+        fn compress(&mut self, _input_buf: &[u8], _output_buf: &mut Vec<u8>) -> Result<()> {
+            Err(nyi_err!("Zstandard compression requires the zstd feature"))
+        }
+    }
+}
+#[cfg(feature = "ruzstd")]
+pub use ruzstd_codec::*;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -390,4 +480,87 @@ mod tests {
     fn test_codec_zstd() {
         test_codec(CodecType::ZSTD);
     }
+
+    #[test]
+    #[cfg(feature = "lz4_flex")]
+    fn test_codec_lz4_flex() {
+        // Frame written by the lz4 command line tool
+        let compressed = [
+            0x04, 0x22, 0x4d, 0x18, 0x64, 0x40, 0xa7, 0x1a, 0x00, 0x00, 0x00, 0xff, 0x00,
+            0x75, 0x6d, 0x62, 0x72, 0x61, 0x20, 0x70, 0x72, 0x6f, 0x66, 0x69, 0x6c, 0x65,
+            0x72, 0x20, 0x0f, 0x00, 0x05, 0x50, 0x66, 0x69, 0x6c, 0x65, 0x72, 0x00, 0x00,
+            0x00, 0x00, 0x3f, 0x0f, 0xdd, 0xe5,
+        ];
+        let expected = b"umbra profiler umbra profiler umbra profiler";
+
+        let mut decompressed = Vec::new();
+        let size = LZ4FlexCodec::new()
+            .decompress(&compressed, &mut decompressed)
+            .expect("Error when decompressing");
+        assert_eq!(expected.len(), size);
+        assert_eq!(&expected[..], decompressed.as_slice());
+
+        // Both implementations read each other's frames
+        let data = random_bytes(10000);
+        let mut compressed = Vec::new();
+        LZ4Codec::new()
+            .compress(&data, &mut compressed)
+            .expect("Error when compressing");
+        let mut decompressed = Vec::new();
+        LZ4FlexCodec::new()
+            .decompress(&compressed, &mut decompressed)
+            .expect("Error when decompressing");
+        assert_eq!(data, decompressed);
+
+        compressed.clear();
+        decompressed.clear();
+        LZ4FlexCodec::new()
+            .compress(&data, &mut compressed)
+            .expect("Error when compressing");
+        LZ4Codec::new()
+            .decompress(&compressed, &mut decompressed)
+            .expect("Error when decompressing");
+        assert_eq!(data, decompressed);
+    }
+
+    #[test]
+    #[cfg(feature = "ruzstd")]
+    fn test_codec_ruzstd() {
+        // Frames written by the zstd command line tool
+        let single_frame = vec![
+            0x28, 0xb5, 0x2f, 0xfd, 0x24, 0x2c, 0xad, 0x00, 0x00, 0x78, 0x75, 0x6d, 0x62,
+            0x72, 0x61, 0x20, 0x70, 0x72, 0x6f, 0x66, 0x69, 0x6c, 0x65, 0x72, 0x20, 0x01,
+            0x00, 0x72, 0xcf, 0x3a, 0x37, 0xb2, 0xfe, 0x77,
+        ];
+        let two_frames = vec![
+            0x28, 0xb5, 0x2f, 0xfd, 0x04, 0x58, 0x29, 0x00, 0x00, 0x75, 0x6d, 0x62, 0x72,
+            0x61, 0xb8, 0x29, 0xe8, 0xec, 0x28, 0xb5, 0x2f, 0xfd, 0x04, 0x58, 0x49, 0x00,
+            0x00, 0x20, 0x70, 0x72, 0x6f, 0x66, 0x69, 0x6c, 0x65, 0x72, 0x11, 0x5e, 0x78,
+            0xbd,
+        ];
+        let cases: Vec<(Vec<u8>, &[u8])> = vec![
+            (single_frame, b"umbra profiler umbra profiler umbra profiler"),
+            (two_frames, b"umbra profiler"),
+        ];
+        for (compressed, expected) in cases {
+            let mut decompressed = Vec::new();
+            let size = RuZstdCodec::new()
+                .decompress(&compressed, &mut decompressed)
+                .expect("Error when decompressing");
+            assert_eq!(expected.len(), size);
+            assert_eq!(expected, decompressed.as_slice());
+        }
+
+        let mut compressed = Vec::new();
+        let data = random_bytes(10000);
+        ZSTDCodec::new()
+            .compress(&data, &mut compressed)
+            .expect("Error when compressing");
+        let mut decompressed = Vec::new();
+        RuZstdCodec::new()
+            .decompress(&compressed, &mut decompressed)
+            .expect("Error when decompressing");
+        assert_eq!(data, decompressed);
+        assert!(RuZstdCodec::new().compress(&data, &mut Vec::new()).is_err());
+    }
 }
@@ -0,0 +1,483 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{ArrayRef, Float64Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::{freq::freq::round, rest::rest_api::find_name},
+    utils::{
+        array_util::{get_floatarray_column, get_stringarray_column},
+        record_batch_schema::RecordBatchSchema,
+        record_batch_util::create_new_record_batch,
+    },
+};
+
+// Metrics are appended to the params of count and freq operations as ;(name=expr;...),
+// e.g. count?operator;(ipc={instructions}/{cycles})
+pub static METRICS_SEPARATOR: &str = ";(";
+
+// Expression over event counts
+// Events are written in braces as their names contain "-", "." and ":"
+// e.g. 1000*{l1-cache-misses}/{mem_inst_retired.all_loads}
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Number(f64),
+    Event(String),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Metric {
+    pub name: String,
+    pub expr: Expr,
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    input: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            pos: 0,
+            input: input,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() != Some(expected) {
+            return Err(format!(
+                "Expected '{}' in metric expression: {}",
+                expected, self.input
+            ));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // expr := term (("+" | "-") term)*
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut left = self.term()?;
+        while let Some(op) = self.peek() {
+            if op != '+' && op != '-' {
+                break;
+            }
+            self.pos += 1;
+            let right = self.term()?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    // term := factor (("*" | "/") factor)*
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut left = self.factor()?;
+        while let Some(op) = self.peek() {
+            if op != '*' && op != '/' {
+                break;
+            }
+            self.pos += 1;
+            let right = self.factor()?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    // factor := number | "{" event "}" | "(" expr ")" | "-" factor
+    fn factor(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Some('{') => {
+                self.pos += 1;
+                let start = self.pos;
+                while self.pos < self.chars.len() && self.chars[self.pos] != '}' {
+                    self.pos += 1;
+                }
+                let event = self.chars[start..self.pos].iter().collect::<String>();
+                self.expect('}')?;
+                Ok(Expr::Event(event))
+            }
+            Some('-') => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.pos;
+                while self.pos < self.chars.len()
+                    && (self.chars[self.pos].is_ascii_digit() || self.chars[self.pos] == '.')
+                {
+                    self.pos += 1;
+                }
+                let number = self.chars[start..self.pos].iter().collect::<String>();
+                number
+                    .parse::<f64>()
+                    .map(Expr::Number)
+                    .map_err(|_| format!("Invalid number {} in metric expression: {}", number, self.input))
+            }
+            _ => Err(format!("Invalid metric expression: {}", self.input)),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> Result<Expr, String> {
+        let mut parser = Parser::new(input);
+        let expr = parser.expr()?;
+        if parser.peek().is_some() {
+            return Err(format!(
+                "Unexpected trailing input in metric expression: {}",
+                input
+            ));
+        }
+        Ok(expr)
+    }
+
+    // Division by zero yields 0, e.g. when an event was not recorded
+    pub fn eval(&self, counts: &HashMap<&str, f64>) -> f64 {
+        match self {
+            Expr::Number(x) => *x,
+            Expr::Event(event) => *counts.get(event.as_str()).unwrap_or(&0.),
+            Expr::Neg(expr) => -expr.eval(counts),
+            Expr::Binary(left, op, right) => {
+                let left = left.eval(counts);
+                let right = right.eval(counts);
+                match op {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    _ => {
+                        if right == 0. {
+                            0.
+                        } else {
+                            left / right
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Metrics are given as (name=expr;name=expr)
+pub fn parse_metrics(params: &str) -> Result<Vec<Metric>, String> {
+    let params = params.trim();
+    let params = params
+        .strip_prefix('(')
+        .and_then(|p| p.strip_suffix(')'))
+        .unwrap_or(params);
+
+    params
+        .split_terminator(";")
+        .map(|metric| {
+            let (name, expr) = metric
+                .split_once("=")
+                .ok_or(format!("Metric needs to be given as name=expression: {}", metric))?;
+            Ok(Metric {
+                name: name.trim().to_string(),
+                expr: Expr::parse(expr)?,
+            })
+        })
+        .collect()
+}
+
+// Params of the operation and the metrics appended to them
+pub fn split_metrics(params: &str) -> (&str, Option<&str>) {
+    match params.find(METRICS_SEPARATOR) {
+        Some(i) => (&params[..i], Some(&params[i + 1..])),
+        None => (params, None),
+    }
+}
+
+// Time buckets as the freq operations count them: the first one ends at start,
+// every sample belongs to the first bucket which ends at or after its time
+pub struct Buckets {
+    pub start: f64,
+    pub bucket_size: f64,
+    // Bucket value of the freq operation from the upper bound of a bucket and the bucket size
+    pub label: fn(f64, f64) -> f64,
+}
+
+// Appends a column per metric to the result of a count or freq operation on batch.
+// A result row takes the event counts of the samples of its group (column group of both batches)
+// and of its time bucket, which is joined on the bucket column of freq results
+pub fn append_metrics(
+    result: &RecordBatch,
+    batch: &RecordBatch,
+    group: Option<&str>,
+    buckets: Option<Buckets>,
+    metrics: &[Metric],
+) -> RecordBatch {
+    let event_column = get_stringarray_column(batch, RecordBatchSchema::EvName as usize);
+    let group_column = group.map(|group| get_stringarray_column(batch, find_name(group, batch)));
+    let result_group_column =
+        group.map(|group| get_stringarray_column(result, find_name(group, result)));
+
+    // Bucket values are truncated, consecutive buckets may share one.
+    // A bucket is identified by its value and the number of earlier buckets with this value
+    let mut occurrences: HashMap<u64, usize> = HashMap::new();
+    let mut next_bucket = |value: f64| {
+        let occurrence = occurrences.entry(value.to_bits()).or_insert(0);
+        *occurrence += 1;
+        (value.to_bits(), *occurrence)
+    };
+
+    // (bucket, group) => event => count
+    let mut hashmap: HashMap<((u64, usize), &str), HashMap<&str, f64>> = HashMap::new();
+    if let Some(buckets) = &buckets {
+        let time_column = get_floatarray_column(batch, find_name("time", batch));
+        let mut time_bucket = buckets.start;
+        let mut bucket = next_bucket((buckets.label)(time_bucket, buckets.bucket_size));
+        for i in 0..batch.num_rows() {
+            while time_bucket < time_column.value(i) {
+                time_bucket += buckets.bucket_size;
+                bucket = next_bucket((buckets.label)(time_bucket, buckets.bucket_size));
+            }
+            let group = group_column.map(|column| column.value(i)).unwrap_or("");
+            let inner_hashmap = hashmap.entry((bucket, group)).or_insert(HashMap::new());
+            *inner_hashmap.entry(event_column.value(i)).or_insert(0.) += 1.;
+        }
+    } else {
+        for i in 0..batch.num_rows() {
+            let group = group_column.map(|column| column.value(i)).unwrap_or("");
+            let inner_hashmap = hashmap.entry(((0, 0), group)).or_insert(HashMap::new());
+            *inner_hashmap.entry(event_column.value(i)).or_insert(0.) += 1.;
+        }
+    }
+    let result_bucket_column = buckets
+        .as_ref()
+        .map(|_| get_floatarray_column(result, find_name("bucket", result)));
+
+    let empty = HashMap::new();
+    let mut result_occurrences: HashMap<(u64, &str), usize> = HashMap::new();
+    let mut metric_vecs = vec![Vec::new(); metrics.len()];
+    for row in 0..result.num_rows() {
+        let group = result_group_column.map(|column| column.value(row)).unwrap_or("");
+        let bucket = match result_bucket_column {
+            Some(column) => {
+                let occurrence = result_occurrences
+                    .entry((column.value(row).to_bits(), group))
+                    .or_insert(0);
+                *occurrence += 1;
+                (column.value(row).to_bits(), *occurrence)
+            }
+            None => (0, 0),
+        };
+        let counts = hashmap.get(&(bucket, group)).unwrap_or(&empty);
+        for (metric, metric_vec) in metrics.iter().zip(metric_vecs.iter_mut()) {
+            metric_vec.push(metric.expr.eval(counts));
+        }
+    }
+
+    let schema = result.schema();
+    let mut field_names = schema
+        .fields()
+        .iter()
+        .map(|field| field.name().as_str())
+        .collect::<Vec<&str>>();
+    let mut data_types = schema
+        .fields()
+        .iter()
+        .map(|field| field.data_type().to_owned())
+        .collect::<Vec<DataType>>();
+    let mut columns = result.columns().to_vec();
+    for (metric, metric_vec) in metrics.iter().zip(metric_vecs) {
+        field_names.push(metric.name.as_str());
+        data_types.push(DataType::Float64);
+        columns.push(Arc::new(Float64Array::from(metric_vec)));
+    }
+
+    create_new_record_batch(field_names, data_types, columns)
+}
+
+// Count events per group (and time bucket) and evaluate the metrics on these counts
+// Output: [bucket], group, count, metric_1, ..., metric_n
+pub fn derived_metrics(
+    batch: &RecordBatch,
+    column_for_group: usize,
+    column_for_time: usize,
+    bucket_size: Option<f64>,
+    metrics: Vec<Metric>,
+) -> RecordBatch {
+    let group_column = get_stringarray_column(batch, column_for_group);
+    let event_column = get_stringarray_column(batch, RecordBatchSchema::EvName as usize);
+    let time_column = get_floatarray_column(batch, column_for_time);
+
+    // (bucket, group) => event => count
+    let mut hashmap: HashMap<(i64, &str), HashMap<&str, f64>> = HashMap::new();
+    let mut groups = BTreeSet::new();
+    let mut max_bucket = 0;
+
+    for i in 0..batch.num_rows() {
+        let group = group_column.value(i);
+        let bucket = if let Some(bucket_size) = bucket_size {
+            (time_column.value(i) / bucket_size).floor() as i64
+        } else {
+            0
+        };
+        max_bucket = max_bucket.max(bucket);
+        groups.insert(group);
+        let inner_hashmap = hashmap.entry((bucket, group)).or_insert(HashMap::new());
+        *inner_hashmap.entry(event_column.value(i)).or_insert(0.) += 1.;
+    }
+
+    let empty = HashMap::new();
+    let mut bucket_vec = Vec::new();
+    let mut group_vec = Vec::new();
+    let mut count_vec = Vec::new();
+    let mut metric_vecs = vec![Vec::new(); metrics.len()];
+
+    for bucket in 0..=max_bucket {
+        for group in &groups {
+            let counts = hashmap.get(&(bucket, *group)).unwrap_or(&empty);
+            if bucket_size.is_none() && counts.is_empty() {
+                continue;
+            }
+            bucket_vec.push(round(bucket as f64 * bucket_size.unwrap_or(0.)));
+            group_vec.push(*group);
+            count_vec.push(counts.values().sum::<f64>());
+            for (metric, metric_vec) in metrics.iter().zip(metric_vecs.iter_mut()) {
+                metric_vec.push(metric.expr.eval(counts));
+            }
+        }
+    }
+
+    let schema = batch.schema();
+    let group_name = schema.field(column_for_group).name();
+
+    let mut field_names = Vec::new();
+    let mut data_types = Vec::new();
+    let mut columns: Vec<ArrayRef> = Vec::new();
+
+    if bucket_size.is_some() {
+        field_names.push("bucket");
+        data_types.push(DataType::Float64);
+        columns.push(Arc::new(Float64Array::from(bucket_vec)));
+    }
+    field_names.push(group_name);
+    data_types.push(DataType::Utf8);
+    columns.push(Arc::new(StringArray::from(group_vec)));
+    field_names.push("count");
+    data_types.push(DataType::Float64);
+    columns.push(Arc::new(Float64Array::from(count_vec)));
+
+    for (metric, metric_vec) in metrics.iter().zip(metric_vecs) {
+        field_names.push(metric.name.as_str());
+        data_types.push(DataType::Float64);
+        columns.push(Arc::new(Float64Array::from(metric_vec)));
+    }
+
+    create_new_record_batch(field_names, data_types, columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exec::rest::rest_api_pars::{abs_freq_pars, count_pars, derived_pars},
+        state::state::set_unfiltered_record_batch,
+        utils::test_util::profile_batch,
+    };
+
+    fn counts() -> HashMap<&'static str, f64> {
+        vec![("cycles", 200.), ("l1-misses", 10.)].into_iter().collect()
+    }
+
+    #[test]
+    fn expressions_respect_precedence() {
+        let expr = Expr::parse("1000 * {l1-misses} / {cycles} - -2").unwrap();
+        assert_eq!(expr.eval(&counts()), 52.);
+        let expr = Expr::parse("({cycles} + {l1-misses}) / ({unknown})").unwrap();
+        assert_eq!(expr.eval(&counts()), 0.);
+    }
+
+    #[test]
+    fn invalid_metrics_are_errors() {
+        for metric in ["{cycles", "1 +", "2 {cycles}", "(1", "1..2", "*"] {
+            assert!(Expr::parse(metric).is_err(), "{}", metric);
+        }
+        assert!(parse_metrics("(ipc)").is_err());
+        let metrics = parse_metrics("(a={cycles};b=2*{cycles})").unwrap();
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[1].name, "b");
+    }
+
+    #[test]
+    fn metrics_are_appended_to_count() {
+        let batch = profile_batch(1000);
+        set_unfiltered_record_batch(batch.clone());
+        let result = count_pars(batch, "operator;(share={cycles}/({cycles}+{loads}))");
+        let count = get_floatarray_column(&result, 1);
+        let share = get_floatarray_column(&result, 2);
+        assert_eq!(result.schema().field(2).name(), "share");
+        for i in 0..result.num_rows() {
+            assert!(count.value(i) > 0.);
+            assert!(share.value(i) > 0. && share.value(i) < 1.);
+        }
+
+        let result = count_pars(profile_batch(10), "operator;(share={cycles}/)");
+        assert_eq!(result.schema().field(0).name(), "error");
+    }
+
+    #[test]
+    fn metrics_are_appended_to_freq_buckets() {
+        let batch = profile_batch(1000);
+        set_unfiltered_record_batch(batch.clone());
+        for (params, freq_column) in [
+            ("ev_name,time:0.5;(n={cycles}+{loads})", 1),
+            ("pipeline,time:0.5!All!All!-1from_to-1;(n={cycles}+{loads})", 3),
+            ("pipeline,time:0.5!All!All!1from_to-1;(n={cycles}+{loads})", 3),
+            ("ev_name,time:0.01;(n={cycles}+{loads})", 1),
+            ("ev_name,time:0.3;(n={cycles}+{loads})", 1),
+            ("pipeline,time:0.03!All!All!0.2from_to-1;(n={cycles}+{loads})", 3),
+        ] {
+            let result = abs_freq_pars(batch.clone(), params);
+            let freq = get_floatarray_column(&result, freq_column);
+            let n = get_floatarray_column(&result, result.num_columns() - 1);
+            assert!(result.num_rows() > 1);
+            assert_eq!(freq.values(), n.values(), "{}", params);
+        }
+    }
+
+    #[test]
+    fn invalid_buckets_are_errors() {
+        let batch = profile_batch(100);
+        set_unfiltered_record_batch(batch.clone());
+        for params in [
+            "ev_name,time:0;(n={cycles})",
+            "ev_name,time:x;(n={cycles})",
+            "ev_name,time;(n={cycles})",
+            "pipeline,time:-1!All!All!-1from_to-1;(n={cycles})",
+            "pipeline,time:0.5!All!All;(n={cycles})",
+            "pipeline,time:0.5!All!All!xfrom_to-1;(n={cycles})",
+        ] {
+            let result = abs_freq_pars(batch.clone(), params);
+            assert_eq!(result.schema().field(0).name(), "error", "{}", params);
+        }
+        for params in ["operator,time:0!(n={cycles})", "operator,time:a!(n={cycles})"] {
+            let result = derived_pars(batch.clone(), params);
+            assert_eq!(result.schema().field(0).name(), "error", "{}", params);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::{collections::BTreeMap, collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{Float64Array, Int64Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::{
+        basic::{uir::round, uir_cost::dump_line},
+        rest::rest_api::find_name,
+    },
+    state::state::get_serde_dict,
+    utils::{
+        array_util::{get_int64_column, get_stringarray_column},
+        record_batch_schema::RecordBatchSchema,
+        record_batch_util::create_new_record_batch,
+    },
+    web_file::serde_reader::DictFields,
+};
+
+// DSOs of profiles without dso column, derived from the srclines
+pub static GENERATED_DSO: &str = "[generated]";
+static UNKNOWN_DSO: &str = "[unknown]";
+static OTHER_DSO: &str = "[other]";
+
+// Class of a binary: generated, libumbra, libc, kernel, unknown or other
+pub fn dso_class(dso: &str) -> &'static str {
+    let name = dso.rsplit('/').next().unwrap_or(dso);
+    if dso == GENERATED_DSO {
+        "generated"
+    } else if dso == UNKNOWN_DSO {
+        "unknown"
+    } else if dso.starts_with("[kernel") || dso.starts_with("[vdso") || name.starts_with("vmlinux") {
+        "kernel"
+    } else if name.contains("umbra") {
+        "libumbra"
+    } else if name.starts_with("libc.")
+        || name.starts_with("libc-")
+        || name.starts_with("libm.")
+        || name.starts_with("libpthread")
+        || name.starts_with("ld-linux")
+        || name.starts_with("libstdc++")
+    {
+        "libc"
+    } else if dso.starts_with("[JIT]")
+        || dso.starts_with("//anon")
+        || dso.starts_with("[anon")
+        || dso.starts_with("memfd:")
+        || (name.starts_with("perf-") && name.ends_with(".map"))
+    {
+        "generated"
+    } else {
+        "other"
+    }
+}
+
+// DSO of a sample derived from its srcline: the generated code (see dump_line) or
+// the binary its source file is compiled into, recognized by the source path
+pub fn srcline_dso(srcline: &str) -> &'static str {
+    let path = srcline.to_lowercase();
+    if dump_line(srcline).is_some() {
+        GENERATED_DSO
+    } else if path.is_empty() || path.starts_with("??") {
+        UNKNOWN_DSO
+    } else if path.starts_with("[kernel")
+        || path.contains("arch/x86/")
+        || path.contains("/linux/")
+        || path.starts_with("kernel/")
+        || path.starts_with("mm/")
+    {
+        "[kernel.kallsyms]"
+    } else if path.contains("umbra") {
+        "libumbra.so"
+    } else if path.contains("glibc") || path.contains("sysdeps/") || path.contains("/libc/") {
+        "libc.so.6"
+    } else if path.contains("include/c++/") || path.contains("libstdc++") {
+        "libstdc++.so.6"
+    } else {
+        OTHER_DSO
+    }
+}
+
+// DSO column for profiles without one, see srcline_dso
+pub fn derive_dso_column(
+    srcline_column: &Int64Array,
+    srclines: &HashMap<u64, String>,
+) -> Vec<&'static str> {
+    let mut cache: HashMap<i64, &'static str> = HashMap::new();
+    (0..srcline_column.len())
+        .map(|i| {
+            let key = srcline_column.value(i);
+            *cache.entry(key).or_insert_with(|| {
+                srclines
+                    .get(&(key as u64))
+                    .map(|srcline| srcline_dso(srcline))
+                    .unwrap_or(UNKNOWN_DSO)
+            })
+        })
+        .collect()
+}
+
+// Class per sample from the dso column, which profiles without it derive at load time
+fn classes(batch: &RecordBatch) -> Vec<&'static str> {
+    let dict = get_serde_dict().unwrap();
+
+    if batch.schema().index_of("dso").is_ok() {
+        // The mapping section resolves dso names to the binary they belong to
+        let dso_column = get_stringarray_column(batch, find_name("dso", batch));
+        return (0..batch.num_rows())
+            .map(|i| {
+                let dso = dso_column.value(i);
+                dso_class(dict.mapping.get(dso).map(|x| x.as_str()).unwrap_or(dso))
+            })
+            .collect();
+    }
+
+    let srclines = dict.dict.get(&(DictFields::Srcline as i64)).unwrap();
+    let srcline_column = get_int64_column(batch, RecordBatchSchema::Uri as usize);
+    derive_dso_column(srcline_column, srclines)
+        .into_iter()
+        .map(dso_class)
+        .collect()
+}
+
+// Samples per binary class and event, with per_pipeline additionally per pipeline.
+// share: share of the samples of the event (and pipeline)
+pub fn dso_breakdown(batch: &RecordBatch, per_pipeline: bool) -> RecordBatch {
+    let event_column = get_stringarray_column(batch, RecordBatchSchema::EvName as usize);
+    let pipeline_column = get_stringarray_column(batch, RecordBatchSchema::Pipeline as usize);
+    let classes = classes(batch);
+
+    let mut counts: BTreeMap<(&str, &str, &str), f64> = BTreeMap::new();
+    let mut totals: HashMap<(&str, &str), f64> = HashMap::new();
+    for i in 0..batch.num_rows() {
+        let pipeline = if per_pipeline {
+            pipeline_column.value(i)
+        } else {
+            "All"
+        };
+        let event = event_column.value(i);
+        *counts.entry((event, pipeline, classes[i])).or_insert(0.) += 1.;
+        *totals.entry((event, pipeline)).or_insert(0.) += 1.;
+    }
+
+    let mut event_vec = Vec::new();
+    let mut pipeline_vec = Vec::new();
+    let mut class_vec = Vec::new();
+    let mut count_vec = Vec::new();
+    let mut share_vec = Vec::new();
+
+    for ((event, pipeline, class), count) in counts {
+        event_vec.push(event);
+        pipeline_vec.push(pipeline);
+        class_vec.push(class);
+        count_vec.push(count);
+        share_vec.push(round(count / totals[&(event, pipeline)]));
+    }
+
+    create_new_record_batch(
+        vec!["ev_name", "pipeline", "dso_class", "count", "share"],
+        vec![
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Float64,
+        ],
+        vec![
+            Arc::new(StringArray::from(event_vec)),
+            Arc::new(StringArray::from(pipeline_vec)),
+            Arc::new(StringArray::from(class_vec)),
+            Arc::new(Float64Array::from(count_vec)),
+            Arc::new(Float64Array::from(share_vec)),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srclines_are_attributed_to_binaries() {
+        for (srcline, class) in [
+            ("dump:12", "generated"),
+            ("??:0", "unknown"),
+            ("", "unknown"),
+            ("umbra::HashJoin::probe /home/ci/umbra/src/execution/HashJoin.cpp:211", "libumbra"),
+            ("__memmove_avx_unaligned_erms ../sysdeps/x86_64/multiarch/memmove-vec.S:312", "libc"),
+            ("std::vector<int>::push_back /usr/include/c++/11/bits/stl_vector.h:1198", "libc"),
+            ("copy_user_generic_unrolled arch/x86/lib/copy_user_64.S:82", "kernel"),
+            ("main /opt/tools/bench.cpp:9", "other"),
+        ] {
+            assert_eq!(dso_class(srcline_dso(srcline)), class, "{}", srcline);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use arrow::error::Result as ArrowResult;
+use arrow::{
+    array::{Array, ArrayRef},
+    compute::{lexsort_to_indices, take, SortColumn},
+    record_batch::RecordBatch,
+};
+
+use crate::utils::array_util::get_stringarray_column;
+
+// Vectorized "GROUP BY": the batch is sorted by all key columns with the arrow kernels,
+// afterwards every group is a contiguous slice of the sorted batch
+
+// "SORT BY" for multiple columns, ascending
+pub fn sort_by_columns(batch: &RecordBatch, columns_for_sort: Vec<usize>) -> RecordBatch {
+    let sort_columns = columns_for_sort
+        .iter()
+        .map(|column| SortColumn {
+            values: batch.column(*column).to_owned(),
+            options: None,
+        })
+        .collect::<Vec<SortColumn>>();
+
+    let sorted_array_of_indices = lexsort_to_indices(&sort_columns, None).unwrap();
+
+    RecordBatch::try_new(
+        batch.schema(),
+        batch
+            .columns()
+            .iter()
+            .map(|column| take(column.as_ref(), &sorted_array_of_indices, None))
+            .collect::<ArrowResult<Vec<ArrayRef>>>()
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+// Ranges (offset, length) of equal values in a string column of a sorted batch
+pub fn group_ranges(sorted_batch: &RecordBatch, column_for_group: usize) -> Vec<(usize, usize)> {
+    let column = get_stringarray_column(sorted_batch, column_for_group);
+
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for i in 1..=column.len() {
+        if i == column.len() || column.value(i) != column.value(start) {
+            ranges.push((start, i - start));
+            start = i;
+        }
+    }
+    ranges
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Float64Array, Int32Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::basic::{
+        derived::Expr,
+        srcline::{parse_srcline, UNKNOWN},
+        uir::round,
+    },
+    state::state::get_serde_dict,
+    utils::{
+        array_util::{get_floatarray_column, get_int64_column, get_stringarray_column},
+        record_batch_schema::RecordBatchSchema,
+        record_batch_util::{create_new_record_batch, error_record_batch},
+        string_util::split_at_comma,
+    },
+    web_file::serde_reader::DictFields,
+};
+
+static ROOT: &str = "root";
+static PATH_SEPARATOR: &str = " > ";
+
+#[derive(Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+// Index of the column name, an error if the batch has no such column of data_type
+fn column_of_type(batch: &RecordBatch, name: &str, data_type: DataType) -> Result<usize, String> {
+    let schema = batch.schema();
+    let index = schema
+        .index_of(name)
+        .map_err(|_| format!("Unknown column {:?}", name))?;
+    if schema.field(index).data_type() != &data_type {
+        return Err(format!("Column {:?} is not of type {:?}", name, data_type));
+    }
+    Ok(index)
+}
+
+// Value of a node of the tree
+enum HierarchyMetric {
+    Count,
+    // sum|avg|min|max(<float column>)
+    Aggregate(Aggregate, usize),
+    // Expression over the event counts of the node, see derived
+    Derived(Expr),
+}
+
+impl HierarchyMetric {
+    fn parse(metric: &str, batch: &RecordBatch) -> Result<HierarchyMetric, String> {
+        let metric = metric.trim();
+        if metric.is_empty() || metric == "count" {
+            return Ok(HierarchyMetric::Count);
+        }
+        for (name, aggregate) in [
+            ("sum", Aggregate::Sum),
+            ("avg", Aggregate::Avg),
+            ("min", Aggregate::Min),
+            ("max", Aggregate::Max),
+        ] {
+            let column = metric
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('('))
+                .and_then(|rest| rest.strip_suffix(')'));
+            if let Some(column) = column {
+                let index = column_of_type(batch, column.trim(), DataType::Float64)?;
+                return Ok(HierarchyMetric::Aggregate(aggregate, index));
+            }
+        }
+        Expr::parse(metric).map(HierarchyMetric::Derived)
+    }
+}
+
+// Level of the tree: a string column or a column derived from the srclines
+enum Level<'a> {
+    Column(&'a StringArray),
+    Derived(Vec<String>),
+}
+
+impl<'a> Level<'a> {
+    fn value(&self, i: usize) -> &str {
+        match self {
+            Level::Column(column) => column.value(i),
+            Level::Derived(values) => values[i].as_str(),
+        }
+    }
+}
+
+// uir_function: function of the generated code, src_file / src_function: source location
+fn srcline_level(batch: &RecordBatch, name: &str) -> Vec<String> {
+    let srcline_column = get_int64_column(batch, RecordBatchSchema::Uri as usize);
+    let dict = get_serde_dict().unwrap();
+    let srclines = dict.dict.get(&(DictFields::Srcline as i64)).unwrap();
+
+    let mut cache: HashMap<i64, String> = HashMap::new();
+    (0..batch.num_rows())
+        .map(|i| {
+            let key = srcline_column.value(i);
+            cache
+                .entry(key)
+                .or_insert_with(|| {
+                    let srcline = srclines.get(&(key as u64)).map(|x| x.as_str()).unwrap_or("");
+                    let location = parse_srcline(srcline, &dict.uir_program);
+                    match name {
+                        "uir_function" if location.kind == "generated" => location.function,
+                        "uir_function" => UNKNOWN.to_string(),
+                        "src_file" => location.file,
+                        _ => location.function,
+                    }
+                })
+                .to_owned()
+        })
+        .collect()
+}
+
+fn level<'a>(batch: &'a RecordBatch, name: &str) -> Result<Level<'a>, String> {
+    match name {
+        "uir_function" | "src_file" | "src_function" => {
+            Ok(Level::Derived(srcline_level(batch, name)))
+        }
+        _ => {
+            let index = column_of_type(batch, name, DataType::Utf8)?;
+            Ok(Level::Column(get_stringarray_column(batch, index)))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Node<'a> {
+    count: f64,
+    sum: f64,
+    min: f64,
+    max: f64,
+    events: HashMap<&'a str, f64>,
+}
+
+impl<'a> Node<'a> {
+    fn add(&mut self, value: f64, event: &'a str) {
+        if self.count == 0. {
+            self.min = value;
+            self.max = value;
+        }
+        self.count += 1.;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        *self.events.entry(event).or_insert(0.) += 1.;
+    }
+
+    fn value(&self, metric: &HierarchyMetric) -> f64 {
+        match metric {
+            HierarchyMetric::Count => self.count,
+            HierarchyMetric::Aggregate(Aggregate::Sum, _) => round(self.sum),
+            HierarchyMetric::Aggregate(Aggregate::Avg, _) => {
+                if self.count == 0. {
+                    0.
+                } else {
+                    round(self.sum / self.count)
+                }
+            }
+            HierarchyMetric::Aggregate(Aggregate::Min, _) => self.min,
+            HierarchyMetric::Aggregate(Aggregate::Max, _) => self.max,
+            HierarchyMetric::Derived(expr) => round(expr.eval(&self.events)),
+        }
+    }
+}
+
+// Tree over the columns in params "col1,...,colN[;metric]", e.g. ev_name,pipeline,operator,physical_op.
+// One row per node: id is the path of the node, parent the id of its parent.
+// The root (id "root") aggregates all samples, every level below it one column.
+// value holds the metric (count if not given), count the samples of the node
+pub fn hierarchy(batch: &RecordBatch, params: &str) -> RecordBatch {
+    let (columns, metric) = params.split_once(';').unwrap_or((params, ""));
+    let names = split_at_comma(columns);
+    let metric = match HierarchyMetric::parse(metric, batch) {
+        Ok(metric) => metric,
+        Err(err) => return error_record_batch(&err),
+    };
+    let levels = match names
+        .iter()
+        .map(|name| level(batch, name.trim()))
+        .collect::<Result<Vec<Level>, String>>()
+    {
+        Ok(levels) => levels,
+        Err(err) => return error_record_batch(&err),
+    };
+
+    let event_column = get_stringarray_column(batch, RecordBatchSchema::EvName as usize);
+    let value_column = match metric {
+        HierarchyMetric::Aggregate(_, column) => Some(get_floatarray_column(batch, column)),
+        _ => None,
+    };
+
+    // Path from the root => node, the root has the empty path
+    let mut nodes: BTreeMap<Vec<&str>, Node> = BTreeMap::new();
+    for i in 0..batch.num_rows() {
+        let value = value_column.map(|column| column.value(i)).unwrap_or(0.);
+        let event = event_column.value(i);
+        let mut path = Vec::with_capacity(levels.len());
+        nodes.entry(path.clone()).or_default().add(value, event);
+        for level in &levels {
+            path.push(level.value(i));
+            nodes.entry(path.clone()).or_default().add(value, event);
+        }
+    }
+
+    let mut id_vec = Vec::new();
+    let mut parent_vec = Vec::new();
+    let mut depth_vec = Vec::new();
+    let mut level_vec = Vec::new();
+    let mut node_vec = Vec::new();
+    let mut value_vec = Vec::new();
+    let mut count_vec = Vec::new();
+
+    let id = |path: &[&str]| {
+        if path.is_empty() {
+            ROOT.to_string()
+        } else {
+            path.join(PATH_SEPARATOR)
+        }
+    };
+
+    for (path, node) in &nodes {
+        id_vec.push(id(path));
+        parent_vec.push(if path.is_empty() {
+            String::new()
+        } else {
+            id(&path[..path.len() - 1])
+        });
+        depth_vec.push(path.len() as i32);
+        level_vec.push(if path.is_empty() {
+            ROOT
+        } else {
+            names[path.len() - 1].trim()
+        });
+        node_vec.push(*path.last().unwrap_or(&ROOT));
+        value_vec.push(node.value(&metric));
+        count_vec.push(node.count);
+    }
+
+    create_new_record_batch(
+        vec!["id", "parent", "depth", "level", "node", "value", "count"],
+        vec![
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Int32,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Float64,
+        ],
+        vec![
+            Arc::new(StringArray::from(id_vec)),
+            Arc::new(StringArray::from(parent_vec)),
+            Arc::new(Int32Array::from(depth_vec)),
+            Arc::new(StringArray::from(level_vec)),
+            Arc::new(StringArray::from(node_vec)),
+            Arc::new(Float64Array::from(value_vec)),
+            Arc::new(Float64Array::from(count_vec)),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_util::profile_batch;
+
+    // id => (parent, value, count)
+    fn nodes_of(result: &RecordBatch) -> HashMap<String, (String, f64, f64)> {
+        let id = get_stringarray_column(result, 0);
+        let parent = get_stringarray_column(result, 1);
+        let value = get_floatarray_column(result, 5);
+        let count = get_floatarray_column(result, 6);
+        (0..result.num_rows())
+            .map(|i| {
+                let node = (parent.value(i).to_string(), value.value(i), count.value(i));
+                (id.value(i).to_string(), node)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn children_add_up_to_their_parent() {
+        let result = hierarchy(&profile_batch(300), "ev_name,pipeline,operator");
+        let nodes = nodes_of(&result);
+        assert_eq!(nodes[ROOT].2, 300.);
+        for (id, (parent, value, count)) in &nodes {
+            assert_eq!(value, count);
+            let children = nodes
+                .values()
+                .filter(|(child_parent, _, _)| child_parent == id)
+                .map(|(_, _, count)| count)
+                .sum::<f64>();
+            if id.matches(PATH_SEPARATOR).count() < 2 {
+                assert_eq!(children, *count, "{}", id);
+            }
+            if id != ROOT {
+                assert!(nodes.contains_key(parent));
+            }
+        }
+        assert!(nodes.contains_key("cycles > pipeline1 > tablescan1"));
+    }
+
+    #[test]
+    fn metrics_of_nodes() {
+        let batch = profile_batch(300);
+        let nodes = nodes_of(&hierarchy(&batch, "ev_name;{cycles}/{loads}"));
+        assert_eq!(nodes["cycles"].1, 0.);
+        assert_eq!(nodes[ROOT].1, round(nodes["cycles"].2 / nodes["loads"].2));
+
+        let nodes = nodes_of(&hierarchy(&batch, "ev_name;max(time)"));
+        let time = get_floatarray_column(&batch, RecordBatchSchema::Time as usize);
+        assert_eq!(nodes[ROOT].1, time.value(batch.num_rows() - 1));
+
+        let result = hierarchy(&batch, "ev_name;{cycles}/");
+        assert_eq!(result.schema().field(0).name(), "error");
+    }
+
+    #[test]
+    fn invalid_columns_are_errors() {
+        let batch = profile_batch(10);
+        for params in [
+            "query",
+            "ev_name,time",
+            "ev_name;sum(query)",
+            "ev_name;sum(pipeline)",
+            "ev_name;avg()",
+        ] {
+            let result = hierarchy(&batch, params);
+            assert_eq!(result.schema().field(0).name(), "error", "{}", params);
+        }
+    }
+}
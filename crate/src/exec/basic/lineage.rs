@@ -0,0 +1,273 @@
+use std::{collections::BTreeMap, collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Float64Array, Int32Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::basic::{op_mapping::init_mapping_operator, uir::round},
+    state::state::{get_mapping_operator, get_serde_dict},
+    utils::{
+        array_util::get_stringarray_column, record_batch_schema::RecordBatchSchema,
+        record_batch_util::create_new_record_batch,
+    },
+    web_file::query_plan::QueryPlan,
+};
+
+// Levels of the operator hierarchy, from the root: pipeline > operator > op_ext > physical_op
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum LineageLevel {
+    Pipeline = 0,
+    Operator = 1,
+    OpExtension = 2,
+    PhysicalOp = 3,
+}
+
+static LEVEL_NAMES: [&str; 4] = ["pipeline", "operator", "op_ext", "physical_op"];
+
+impl LineageLevel {
+    pub fn from_name(name: &str) -> Result<LineageLevel, String> {
+        match name {
+            "pipeline" => Ok(LineageLevel::Pipeline),
+            "operator" => Ok(LineageLevel::Operator),
+            "op_ext" => Ok(LineageLevel::OpExtension),
+            "physical_op" => Ok(LineageLevel::PhysicalOp),
+            _ => Err(format!(
+                "Unsupported lineage level {}, expected one of {}",
+                name,
+                LEVEL_NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+// Samples per node of the hierarchy: path from the root => count
+fn count_per_path(batch: &RecordBatch, level: LineageLevel) -> BTreeMap<Vec<&str>, f64> {
+    let columns = [
+        get_stringarray_column(batch, RecordBatchSchema::Pipeline as usize),
+        get_stringarray_column(batch, RecordBatchSchema::Operator as usize),
+        get_stringarray_column(batch, RecordBatchSchema::OpExtension as usize),
+        get_stringarray_column(batch, RecordBatchSchema::Physical as usize),
+    ];
+
+    let mut counts = BTreeMap::new();
+    for i in 0..batch.num_rows() {
+        let path = columns[..=level as usize]
+            .iter()
+            .map(|column| column.value(i))
+            .collect::<Vec<&str>>();
+        *counts.entry(path).or_insert(0.) += 1.;
+    }
+    counts
+}
+
+// Samples rolled up to a level of physical_op > op_ext > operator > pipeline.
+// With parent only the nodes below it (at any depth) are returned.
+// Every node carries its parent chain (path), share is its share of the parent,
+// total_share its share of all samples.
+// From the operator level on, plan_path holds the operators of the query plan from its root
+// down to the operator and plan_parent the operator consuming its output, empty if not in the plan
+pub fn lineage(batch: &RecordBatch, level: LineageLevel, parent: Option<&str>) -> RecordBatch {
+    init_mapping_operator();
+    let mapping = get_mapping_operator();
+    let mapping = mapping.lock().unwrap();
+    let dict = get_serde_dict();
+    let empty = QueryPlan::default();
+    let plan = dict
+        .as_ref()
+        .and_then(|dict| dict.query_plan.as_ref())
+        .unwrap_or(&empty);
+
+    let counts = count_per_path(batch, level);
+    let total = batch.num_rows() as f64;
+
+    // Counts of the parents, a parent is the path without its last node
+    let mut parent_counts: HashMap<&[&str], f64> = HashMap::new();
+    for (path, count) in &counts {
+        *parent_counts.entry(&path[..path.len() - 1]).or_insert(0.) += count;
+    }
+
+    let depth = level as usize + 1;
+    let mut level_vecs: Vec<Vec<&str>> = vec![Vec::new(); depth];
+    let mut nice_op_vec = Vec::new();
+    let mut plan_path_vec = Vec::new();
+    let mut plan_parent_vec = Vec::new();
+    let mut path_vec = Vec::new();
+    let mut count_vec = Vec::new();
+    let mut share_vec = Vec::new();
+    let mut total_share_vec = Vec::new();
+
+    for (path, count) in &counts {
+        if let Some(parent) = parent {
+            if !path[..path.len() - 1].contains(&parent) {
+                continue;
+            }
+        }
+        for (level_vec, node) in level_vecs.iter_mut().zip(path) {
+            level_vec.push(node);
+        }
+        if level >= LineageLevel::Operator {
+            nice_op_vec.push(mapping.get(path[1]).map(|x| x.as_str()).unwrap_or(path[1]));
+            let plan_path = plan.path_of_operator(path[1]);
+            plan_parent_vec.push(if plan_path.len() > 1 {
+                plan_path[plan_path.len() - 2]
+            } else {
+                ""
+            });
+            plan_path_vec.push(plan_path.join(" > "));
+        }
+        path_vec.push(path.join(" > "));
+        count_vec.push(*count);
+        share_vec.push(round(count / parent_counts[&path[..path.len() - 1]]));
+        total_share_vec.push(round(count / total));
+    }
+
+    let mut field_names = LEVEL_NAMES[..depth].to_vec();
+    let mut data_types = vec![DataType::Utf8; depth];
+    let mut columns: Vec<ArrayRef> = level_vecs
+        .into_iter()
+        .map(|level_vec| Arc::new(StringArray::from(level_vec)) as ArrayRef)
+        .collect();
+    if level >= LineageLevel::Operator {
+        field_names.extend(["nice_op", "plan_parent", "plan_path"]);
+        data_types.extend([DataType::Utf8, DataType::Utf8, DataType::Utf8]);
+        columns.push(Arc::new(StringArray::from(nice_op_vec)));
+        columns.push(Arc::new(StringArray::from(plan_parent_vec)));
+        columns.push(Arc::new(StringArray::from(plan_path_vec)));
+    }
+    field_names.extend(["path", "count", "share", "total_share"]);
+    data_types.extend([
+        DataType::Utf8,
+        DataType::Float64,
+        DataType::Float64,
+        DataType::Float64,
+    ]);
+    columns.push(Arc::new(StringArray::from(path_vec)));
+    columns.push(Arc::new(Float64Array::from(count_vec)));
+    columns.push(Arc::new(Float64Array::from(share_vec)));
+    columns.push(Arc::new(Float64Array::from(total_share_vec)));
+
+    create_new_record_batch(field_names, data_types, columns)
+}
+
+// Physical implementations inside every logical operator, over all pipelines.
+// share: share of the samples of the operator, dominant marks the implementation with most samples
+pub fn physical_breakdown(batch: &RecordBatch) -> RecordBatch {
+    init_mapping_operator();
+    let mapping = get_mapping_operator();
+    let mapping = mapping.lock().unwrap();
+
+    let operator_column = get_stringarray_column(batch, RecordBatchSchema::Operator as usize);
+    let physical_column = get_stringarray_column(batch, RecordBatchSchema::Physical as usize);
+
+    let mut counts: BTreeMap<&str, BTreeMap<&str, f64>> = BTreeMap::new();
+    for i in 0..batch.num_rows() {
+        *counts
+            .entry(operator_column.value(i))
+            .or_insert(BTreeMap::new())
+            .entry(physical_column.value(i))
+            .or_insert(0.) += 1.;
+    }
+
+    let mut operator_vec = Vec::new();
+    let mut nice_op_vec = Vec::new();
+    let mut physical_vec = Vec::new();
+    let mut count_vec = Vec::new();
+    let mut share_vec = Vec::new();
+    let mut dominant_vec = Vec::new();
+
+    for (operator, physical_ops) in &counts {
+        let total = physical_ops.values().sum::<f64>();
+        let max = physical_ops.values().cloned().fold(0., f64::max);
+        // Ties: the first implementation in name order dominates
+        let mut dominant_found = false;
+        for (physical_op, count) in physical_ops {
+            let dominant = !dominant_found && *count == max;
+            dominant_found |= dominant;
+            operator_vec.push(*operator);
+            nice_op_vec.push(mapping.get(*operator).map(|x| x.as_str()).unwrap_or(operator));
+            physical_vec.push(*physical_op);
+            count_vec.push(*count);
+            share_vec.push(round(count / total));
+            dominant_vec.push(dominant as i32);
+        }
+    }
+
+    create_new_record_batch(
+        vec!["operator", "nice_op", "physical_op", "count", "share", "dominant"],
+        vec![
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Int32,
+        ],
+        vec![
+            Arc::new(StringArray::from(operator_vec)),
+            Arc::new(StringArray::from(nice_op_vec)),
+            Arc::new(StringArray::from(physical_vec)),
+            Arc::new(Float64Array::from(count_vec)),
+            Arc::new(Float64Array::from(share_vec)),
+            Arc::new(Int32Array::from(dominant_vec)),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        state::state::{set_serde_dict, set_unfiltered_record_batch},
+        utils::{array_util::get_floatarray_column, test_util::profile_batch},
+        web_file::serde_reader::SerdeDict,
+    };
+
+    fn load_profile() -> RecordBatch {
+        let batch = profile_batch(200);
+        set_unfiltered_record_batch(batch.clone());
+        let plan = r#"{"operator": "groupby3", "input": {"operator": "hashjoin2",
+            "left": {"operator": "tablescan1"}, "right": {"operator": "tablescan4"}}}"#;
+        set_serde_dict(SerdeDict {
+            dict: HashMap::new(),
+            query_plan: QueryPlan::parse(plan),
+            uir_program: Default::default(),
+            mapping: HashMap::new(),
+            query_plan_json: plan.to_string(),
+        });
+        batch
+    }
+
+    #[test]
+    fn levels_are_rolled_up_with_the_plan_chain() {
+        let batch = load_profile();
+        for name in LEVEL_NAMES {
+            let level = LineageLevel::from_name(name).unwrap();
+            let result = lineage(&batch, level, None);
+            let count = get_floatarray_column(&result, find_column(&result, "count"));
+            assert_eq!(count.values().iter().sum::<f64>(), 200., "{}", name);
+        }
+        assert!(LineageLevel::from_name("query").is_err());
+
+        let result = lineage(&batch, LineageLevel::OpExtension, Some("pipeline1"));
+        let operator = get_stringarray_column(&result, 1);
+        let plan_parent = get_stringarray_column(&result, find_column(&result, "plan_parent"));
+        let plan_path = get_stringarray_column(&result, find_column(&result, "plan_path"));
+        for i in 0..result.num_rows() {
+            let (parent, path) = match operator.value(i) {
+                "tablescan1" => ("hashjoin2", "groupby3 > hashjoin2 > tablescan1"),
+                "hashjoin2" => ("groupby3", "groupby3 > hashjoin2"),
+                "groupby3" => ("", "groupby3"),
+                _ => ("", ""),
+            };
+            assert_eq!(plan_parent.value(i), parent);
+            assert_eq!(plan_path.value(i), path);
+        }
+    }
+
+    fn find_column(batch: &RecordBatch, name: &str) -> usize {
+        batch.schema().index_of(name).unwrap()
+    }
+}
@@ -1,16 +1,36 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Read,
+    sync::Arc,
+};
 
+use arrow::{array::StringArray, datatypes::DataType, record_batch::RecordBatch};
 use regex::Regex;
 
 use crate::{
-    state::state::{get_mapping_operator, get_unfiltered_record_batch, insert_mapping_hashmap},
-    utils::{array_util::get_stringarray_column, record_batch_schema::RecordBatchSchema},
+    state::state::{
+        get_mapping_operator, get_operator_table, get_unfiltered_record_batch,
+        get_user_operator_table, insert_mapping_hashmap, set_operator_table,
+        set_user_operator_table,
+    },
+    utils::{
+        array_util::get_stringarray_column, record_batch_schema::RecordBatchSchema,
+        record_batch_util::create_new_record_batch,
+    },
+    web_file::{
+        validation::{add_validation_issue, Severity},
+        zip_entry::ZipEntry,
+    },
 };
 
-use super::{basic::find_unique_string, filter::filter_with};
+// Operator file of the archive, kinds it lacks fall back to the default table
+pub static OPERATOR_MAPPING_FILE_NAME: &str = "operator_mapping.json";
+// Symbol of operator kinds missing in the table
+pub static FALLBACK_SYMBOL: &str = "\u{25cb}";
 
-pub fn operator_hashmap() -> HashMap<&'static str, String> {
-    let hashmap: HashMap<&str, String> = [
+// Default table: operator kind => symbol
+pub fn operator_hashmap() -> HashMap<String, String> {
+    let hashmap: HashMap<String, String> = [
         ("tablescan", '\u{2637}'.to_string()),
         ("groupbyscan", '\u{2637}'.to_string()),
         ("groupby", '\u{0393}'.to_string()),
@@ -30,40 +50,102 @@ pub fn operator_hashmap() -> HashMap<&'static str, String> {
         ("rightsemi", '\u{22ca}'.to_string()),
         ("leftanti", '\u{25b7}'.to_string()),
         ("rightanti", '\u{25c1}'.to_string()),
+        ("window", '\u{03c9}'.to_string()),
+        ("iteration", '\u{21bb}'.to_string()),
+        ("earlyprobe", "\u{2a1d}e".to_string()),
     ]
     .iter()
-    .cloned()
+    .map(|(kind, symbol)| (kind.to_string(), symbol.to_owned()))
     .collect();
     return hashmap;
 }
 
-fn remove_nums_fr_str(str: &str) -> String {
+// Table as JSON object, operator kind => symbol
+fn parse_operator_table(json: &str) -> Result<HashMap<String, String>, String> {
+    serde_json::from_str::<HashMap<String, String>>(json).map_err(|err| err.to_string())
+}
+
+// Default table extended by the table of the archive and the one of the user
+pub fn load_operator_table(file_size: u64) {
+    let json = ZipEntry::open(OPERATOR_MAPPING_FILE_NAME, file_size).map(|entry| {
+        let mut json = String::new();
+        let _result = entry.reader().read_to_string(&mut json);
+        json
+    });
+    set_operator_table(build_operator_table(json.as_deref()));
+}
+
+fn build_operator_table(archive_json: Option<&str>) -> HashMap<String, String> {
+    let mut table = operator_hashmap();
+    match archive_json.map(parse_operator_table) {
+        Some(Ok(archive_table)) => table.extend(archive_table),
+        Some(Err(err)) => add_validation_issue(
+            Severity::Warning,
+            OPERATOR_MAPPING_FILE_NAME,
+            format!("Invalid operator mapping, default symbols are used: {}", err),
+            1,
+        ),
+        None => {}
+    }
+    table.extend(get_user_operator_table());
+    table
+}
+
+// Table supplied by the user, extends the current one and the ones of later archives
+pub fn extend_operator_table(json: &str) -> Result<(), String> {
+    let user_table = parse_operator_table(json)?;
+    let mut user_operator_table = get_user_operator_table();
+    user_operator_table.extend(user_table.to_owned());
+    set_user_operator_table(user_operator_table);
+
+    let table = get_operator_table();
+    let mut table = table.lock().unwrap().to_owned();
+    table.extend(user_table);
+    set_operator_table(table);
+    Ok(())
+}
+
+fn operator_symbol(table: &HashMap<String, String>, kind: &str) -> String {
+    table
+        .get(kind)
+        .map(|symbol| symbol.to_owned())
+        .unwrap_or(FALLBACK_SYMBOL.to_string())
+}
+
+// Operator kind of an operator id, e.g. tablescan of tablescan12
+pub fn operator_kind(str: &str) -> String {
     let re = Regex::new("[0123456789]").unwrap();
-    let cow_str = re.replace(str, "");
+    let cow_str = re.replace_all(str, "");
     let str_to = cow_str.to_string();
     return str_to;
 }
 
-fn get_nice_op_null(op_id: &str, clean_op_id: String) -> String {
-    if op_id == "Kernel" || op_id == "No Operator" {
+fn is_no_operator(op_id: &str) -> bool {
+    op_id == "Kernel" || op_id == "No Operator"
+}
+
+fn get_nice_op_null(table: &HashMap<String, String>, op_id: &str, clean_op_id: String) -> String {
+    if is_no_operator(op_id) {
         return "-".to_string();
     } else {
-        let mut str = operator_hashmap()
-            .get(clean_op_id.as_str())
-            .unwrap_or(&"".to_string())
-            .to_owned();
+        let mut str = operator_symbol(table, clean_op_id.as_str());
         str.push_str(" ");
         str.push_str(clean_op_id.as_str());
         return str;
     }
 }
 
-fn get_nice_op(op_id: &str, clean_op_id: String, op_extens: String) -> String {
+fn get_nice_op(
+    table: &HashMap<String, String>,
+    op_id: &str,
+    clean_op_id: String,
+    op_extens: String,
+) -> String {
     let nice_op = if op_extens == "null" {
-        get_nice_op_null(op_id, clean_op_id)
+        get_nice_op_null(table, op_id, clean_op_id)
     } else {
         let out = if op_id.contains("tablescan") {
-            let mut str = operator_hashmap().get("tablescan").unwrap().to_owned();
+            let mut str = operator_symbol(table, "tablescan");
             str.push_str(" ");
             str.push_str(&op_extens);
             str.push_str(" ");
@@ -72,10 +154,7 @@ fn get_nice_op(op_id: &str, clean_op_id: String, op_extens: String) -> String {
         } else {
             let str = op_extens.to_owned();
             let split = str.split_terminator("-").collect::<Vec<&str>>();
-            let mut str = operator_hashmap()
-                .get(split[0])
-                .unwrap_or(&"".to_string())
-                .to_owned();
+            let mut str = operator_symbol(table, split[0]);
             str.push_str(" ");
             str.push_str(&op_extens);
             str.push_str(" ");
@@ -87,6 +166,28 @@ fn get_nice_op(op_id: &str, clean_op_id: String, op_extens: String) -> String {
     nice_op
 }
 
+// First extension and physical operator of every operator, in one pass over the samples
+fn operator_details(batch: &RecordBatch) -> BTreeMap<String, (String, String)> {
+    let operator_column = get_stringarray_column(batch, RecordBatchSchema::Operator as usize);
+    let extension_column = get_stringarray_column(batch, RecordBatchSchema::OpExtension as usize);
+    let physical_column = get_stringarray_column(batch, RecordBatchSchema::Physical as usize);
+
+    let mut details = BTreeMap::new();
+    for i in 0..batch.num_rows() {
+        let op_id = operator_column.value(i);
+        if !details.contains_key(op_id) {
+            details.insert(
+                op_id.to_string(),
+                (
+                    extension_column.value(i).to_string(),
+                    physical_column.value(i).to_string(),
+                ),
+            );
+        }
+    }
+    details
+}
+
 pub fn init_mapping_operator() {
     let mapping = get_mapping_operator();
     let map = mapping.lock().unwrap();
@@ -95,32 +196,114 @@ pub fn init_mapping_operator() {
         return;
     }
 
-    let unique_batch = find_unique_string(
-        &get_unfiltered_record_batch().unwrap().batch,
-        RecordBatchSchema::Operator as usize,
-    );
+    let table = get_operator_table();
+    let table = table.lock().unwrap();
+    let mut hashmap = HashMap::new();
 
-    let vec = get_stringarray_column(&unique_batch, 0);
+    for (op_id, (op_extens, _)) in operator_details(&get_unfiltered_record_batch().unwrap().batch) {
+        let clean_op_id = operator_kind(&op_id);
+        let nice_op = get_nice_op(&table, &op_id, clean_op_id, op_extens);
+        hashmap.insert(op_id, nice_op);
+    }
 
-    let mut hashmap = HashMap::new();
+    insert_mapping_hashmap(hashmap);
+}
 
-    for op_id in vec {
-        let group_batch = filter_with(
-            RecordBatchSchema::Operator as usize,
-            vec![op_id.unwrap()],
-            &get_unfiltered_record_batch().unwrap().batch,
-        );
+// Id, kind, symbol, extension and physical operator of every operator of the profile
+pub fn operator_info(batch: &RecordBatch) -> RecordBatch {
+    init_mapping_operator();
+    let mapping = get_mapping_operator();
+    let mapping = mapping.lock().unwrap();
+    let table = get_operator_table();
+    let table = table.lock().unwrap();
+
+    let mut id_vec = Vec::new();
+    let mut kind_vec = Vec::new();
+    let mut symbol_vec = Vec::new();
+    let mut extension_vec = Vec::new();
+    let mut physical_vec = Vec::new();
+    let mut nice_vec = Vec::new();
+
+    for (op_id, (op_extens, physical_op)) in operator_details(batch) {
+        let kind = operator_kind(&op_id);
+        let symbol = if is_no_operator(&op_id) {
+            "-".to_string()
+        } else {
+            operator_symbol(&table, &kind)
+        };
+        nice_vec.push(mapping.get(&op_id).cloned().unwrap_or(op_id.to_owned()));
+        id_vec.push(op_id);
+        kind_vec.push(kind);
+        symbol_vec.push(symbol);
+        extension_vec.push(op_extens);
+        physical_vec.push(physical_op);
+    }
 
-        let op_extens_col =
-            get_stringarray_column(&group_batch, RecordBatchSchema::OpExtension as usize);
-        let op_extens = op_extens_col.value(0).to_owned();
-        let op_id = op_id.unwrap();
-        let clean_op_id = remove_nums_fr_str(op_id);
+    create_new_record_batch(
+        vec!["operator", "kind", "symbol", "extension", "physical_op", "nice_op"],
+        vec![
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+        ],
+        vec![
+            Arc::new(StringArray::from(id_vec)),
+            Arc::new(StringArray::from(kind_vec)),
+            Arc::new(StringArray::from(symbol_vec)),
+            Arc::new(StringArray::from(extension_vec)),
+            Arc::new(StringArray::from(physical_vec)),
+            Arc::new(StringArray::from(nice_vec)),
+        ],
+    )
+}
 
-        let nice_op = get_nice_op(op_id, clean_op_id, op_extens);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{state::state::set_unfiltered_record_batch, utils::test_util::profile_batch};
 
-        hashmap.insert(op_id.to_string(), nice_op);
+    #[test]
+    fn kinds_drop_the_operator_number() {
+        assert_eq!(operator_kind("hashjoin12"), "hashjoin");
+        assert_eq!(operator_kind("tablescan1"), "tablescan");
+        assert_eq!(operator_kind("No operator"), "No operator");
     }
 
-    insert_mapping_hashmap(hashmap);
+    #[test]
+    fn user_symbols_outlast_the_archive_table() {
+        extend_operator_table(r#"{"groupby": "G", "hashjoin": "H"}"#).unwrap();
+        assert!(extend_operator_table("[]").is_err());
+        let table = build_operator_table(Some(r#"{"groupby": "A", "map": "M"}"#));
+        assert_eq!(table["groupby"], "G");
+        assert_eq!(table["hashjoin"], "H");
+        assert_eq!(table["map"], "M");
+        assert_eq!(table["sort"], operator_hashmap()["sort"]);
+    }
+
+    #[test]
+    fn unknown_kinds_get_the_fallback_symbol() {
+        set_operator_table(build_operator_table(None));
+        let batch = profile_batch(100);
+        set_unfiltered_record_batch(batch.clone());
+        let info = operator_info(&batch);
+        let operator = get_stringarray_column(&info, 0);
+        let kind = get_stringarray_column(&info, 1);
+        let symbol = get_stringarray_column(&info, 2);
+        let rows = (0..info.num_rows())
+            .map(|i| (operator.value(i), kind.value(i), symbol.value(i)))
+            .collect::<Vec<_>>();
+        let table = operator_hashmap();
+        assert_eq!(
+            rows,
+            [
+                ("No operator", "No operator", FALLBACK_SYMBOL),
+                ("groupby3", "groupby", table["groupby"].as_str()),
+                ("hashjoin2", "hashjoin", FALLBACK_SYMBOL),
+                ("tablescan1", "tablescan", table["tablescan"].as_str()),
+            ]
+        );
+    }
 }
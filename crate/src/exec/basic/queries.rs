@@ -0,0 +1,229 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use arrow::{
+    array::{Float64Array, Int32Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use parquet::{
+    arrow::{ArrowReader, ParquetFileArrowReader},
+    file::serialized_reader::SerializedFileReader,
+};
+
+use crate::{
+    exec::freq::freq::round,
+    state::state::{
+        get_archive_queries, get_file_size, get_query_summary, get_selected_query,
+        get_unfiltered_record_batch, set_query_summary,
+    },
+    utils::{
+        array_util::{get_floatarray_column, get_int64_column, get_stringarray_column},
+        record_batch_schema::RecordBatchSchema,
+        record_batch_util::{
+            create_new_record_batch, error_record_batch, EXCLUDED_OPERATOR, UNKNOWN_DICT_KEY,
+        },
+    },
+    web_file::{archive::QueryFiles, serde_reader::SerdeDict, web_file_chunkreader::WebFileChunkReader},
+};
+
+// Queries of the archive, selected marks the loaded query
+pub fn query_list() -> RecordBatch {
+    let queries = get_archive_queries();
+    let selected = get_selected_query();
+
+    let mut query_vec = Vec::new();
+    let mut selected_vec = Vec::new();
+    for (i, query) in queries.iter().enumerate() {
+        query_vec.push(query.name.as_str());
+        selected_vec.push((i == selected) as i32);
+    }
+
+    create_new_record_batch(
+        vec!["query", "selected"],
+        vec![DataType::Utf8, DataType::Int32],
+        vec![
+            Arc::new(StringArray::from(query_vec)),
+            Arc::new(Int32Array::from(selected_vec)),
+        ],
+    )
+}
+
+// Columns of samples.parquet with the operator id, the time and the event id of a sample
+static OPERATOR_COLUMN: usize = 0;
+static TIME_COLUMN: usize = 2;
+static EVENT_COLUMN: usize = 3;
+
+// event => (count, first, last)
+type Totals = BTreeMap<String, (f64, f64, f64)>;
+
+fn add_sample(totals: &mut Totals, event: &str, time: f64) {
+    let entry = totals.entry(event.to_string()).or_insert((0., time, time));
+    entry.0 += 1.;
+    entry.1 = entry.1.min(time);
+    entry.2 = entry.2.max(time);
+}
+
+// Samples and duration per event of a query, without the samples of EXCLUDED_OPERATOR
+// like every query
+fn totals_per_event(batch: &RecordBatch) -> Totals {
+    let operator_column = get_stringarray_column(batch, RecordBatchSchema::Operator as usize);
+    let event_column = get_stringarray_column(batch, RecordBatchSchema::EvName as usize);
+    let time_column = get_floatarray_column(batch, RecordBatchSchema::Time as usize);
+
+    let mut totals = Totals::new();
+    for i in 0..batch.num_rows() {
+        if operator_column.value(i) != EXCLUDED_OPERATOR {
+            add_sample(&mut totals, event_column.value(i), time_column.value(i));
+        }
+    }
+    totals
+}
+
+// Reads only operator, time and event of the samples of a query which is not loaded,
+// an unreadable samples file has no totals
+fn totals_of_file(files: &QueryFiles, file_size: u64) -> Totals {
+    let (events, operators) = SerdeDict::read_event_and_operator_names(&files.dictionary, file_size);
+    let mut totals = Totals::new();
+    let reader = match SerializedFileReader::new(WebFileChunkReader::of_file(&files.samples, file_size)) {
+        Ok(reader) => reader,
+        Err(_) => return totals,
+    };
+    let mut reader = ParquetFileArrowReader::new(Arc::new(reader));
+    let record_reader =
+        match reader.get_record_reader_by_columns(
+            vec![OPERATOR_COLUMN, TIME_COLUMN, EVENT_COLUMN].into_iter(),
+            1024 * 8,
+        ) {
+            Ok(record_reader) => record_reader,
+            Err(_) => return totals,
+        };
+
+    // The reader returns the columns in the order of the file
+    for batch in record_reader.flatten() {
+        let operator_column = get_int64_column(&batch, 0);
+        let time_column = get_floatarray_column(&batch, 1);
+        let event_column = get_int64_column(&batch, 2);
+        for i in 0..batch.num_rows() {
+            let operator = operators.get(&(operator_column.value(i) as u64));
+            if operator.map(|operator| operator.as_str()) == Some(EXCLUDED_OPERATOR) {
+                continue;
+            }
+            let event = events
+                .get(&(event_column.value(i) as u64))
+                .map(|event| event.as_str())
+                .unwrap_or(UNKNOWN_DICT_KEY);
+            add_sample(&mut totals, event, time_column.value(i));
+        }
+    }
+    totals
+}
+
+// Totals of all queries of the archive, the loaded query and the global state stay untouched
+fn totals_per_query() -> Vec<(String, Totals)> {
+    let file_size = get_file_size().unwrap_or(0);
+    let selected = get_selected_query();
+    let unfiltered = get_unfiltered_record_batch();
+
+    get_archive_queries()
+        .iter()
+        .enumerate()
+        .map(|(i, query)| {
+            let totals = match &unfiltered {
+                Some(batch) if i == selected => totals_per_event(&batch.batch),
+                _ => totals_of_file(query, file_size),
+            };
+            (query.name.to_owned(), totals)
+        })
+        .collect()
+}
+
+// Per-query totals of a benchmark run
+// share: samples of the query of all samples of the event in the archive
+pub fn query_summary() -> RecordBatch {
+    if let Some(summary) = get_query_summary() {
+        return summary;
+    }
+    // The samples of the other queries are only read from the archive
+    if get_file_size().is_none() && get_archive_queries().len() > 1 {
+        return error_record_batch("The query summary needs the archive");
+    }
+    let totals = totals_per_query();
+
+    let mut event_totals: BTreeMap<&str, f64> = BTreeMap::new();
+    for (_, events) in &totals {
+        for (event, (count, _, _)) in events {
+            *event_totals.entry(event).or_insert(0.) += count;
+        }
+    }
+
+    let mut query_vec = Vec::new();
+    let mut event_vec = Vec::new();
+    let mut count_vec = Vec::new();
+    let mut duration_vec = Vec::new();
+    let mut share_vec = Vec::new();
+
+    for (query, events) in &totals {
+        for (event, (count, first, last)) in events {
+            query_vec.push(query.as_str());
+            event_vec.push(event.as_str());
+            count_vec.push(*count);
+            duration_vec.push(round(last - first));
+            share_vec.push(round(count / event_totals[event.as_str()]));
+        }
+    }
+
+    let summary = create_new_record_batch(
+        vec!["query", "ev_name", "count", "duration", "share"],
+        vec![
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+        ],
+        vec![
+            Arc::new(StringArray::from(query_vec)),
+            Arc::new(StringArray::from(event_vec)),
+            Arc::new(Float64Array::from(count_vec)),
+            Arc::new(Float64Array::from(duration_vec)),
+            Arc::new(Float64Array::from(share_vec)),
+        ],
+    );
+    set_query_summary(summary.clone());
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{state::state::set_unfiltered_record_batch, utils::test_util::profile_batch};
+
+    #[test]
+    fn summary_of_loaded_query_is_cached() {
+        set_unfiltered_record_batch(profile_batch(100));
+        let summary = query_summary();
+        let count = get_floatarray_column(&summary, 2);
+        let share = get_floatarray_column(&summary, 4);
+        assert_eq!(summary.num_rows(), 2);
+        assert_eq!(count.values().iter().sum::<f64>(), 100.);
+        assert_eq!(share.values(), &[1., 1.]);
+
+        set_unfiltered_record_batch(profile_batch(10));
+        assert_eq!(query_summary(), summary);
+    }
+
+    #[test]
+    fn excluded_operator_is_not_counted() {
+        let batch = profile_batch(10);
+        let operators = (0..10)
+            .map(|i| if i < 3 { EXCLUDED_OPERATOR } else { "tablescan1" })
+            .collect::<Vec<&str>>();
+        let mut columns = batch.columns().to_vec();
+        columns[RecordBatchSchema::Operator as usize] = Arc::new(StringArray::from(operators));
+        let batch = RecordBatch::try_new(batch.schema(), columns).unwrap();
+
+        let totals = totals_per_event(&batch);
+        assert_eq!(totals.values().map(|(count, _, _)| count).sum::<f64>(), 7.);
+    }
+}
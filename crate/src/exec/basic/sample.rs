@@ -0,0 +1,169 @@
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, BooleanArray, Float64Array},
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::basic::derived::METRICS_SEPARATOR,
+    state::state::SampleBatch,
+    utils::{
+        array_util::{get_floatarray_column, get_stringarray_column},
+        record_batch_schema::RecordBatchSchema,
+    },
+};
+
+// Profiles with more samples get a sample of about this size for approximate answers
+pub static SAMPLE_ROWS: usize = 500_000;
+// Operations counting samples, only their results can be scaled to the whole profile
+static APPROXIMATE_OPERATIONS: [&str; 4] = ["count", "basic_count", "sunburst", "absfreq"];
+// Count columns of the results of these operations
+static COUNT_COLUMNS: [&str; 5] = ["count", "pipecount", "opcount", "absfreq", "absfreqNEG"];
+// Normal quantile of the 95% confidence interval
+static Z_95: f64 = 1.96;
+
+// Stratified by event and pipeline with the same rate in every stratum (proportional allocation).
+// Rows are taken at even distances inside their stratum, which keeps the time distribution.
+// None if the batch is small enough to be queried exactly
+pub fn build_sample(batch: &RecordBatch) -> Option<SampleBatch> {
+    if batch.num_rows() <= SAMPLE_ROWS {
+        return None;
+    }
+    let rate = SAMPLE_ROWS as f64 / batch.num_rows() as f64;
+    let event_column = get_stringarray_column(batch, RecordBatchSchema::EvName as usize);
+    let pipeline_column = get_stringarray_column(batch, RecordBatchSchema::Pipeline as usize);
+
+    // Rows seen per stratum
+    let mut seen: HashMap<(&str, &str), f64> = HashMap::new();
+    let selection = (0..batch.num_rows())
+        .map(|i| {
+            let j = seen
+                .entry((event_column.value(i), pipeline_column.value(i)))
+                .or_insert(0.);
+            let selected = ((*j + 1.) * rate).floor() > (*j * rate).floor();
+            *j += 1.;
+            Some(selected)
+        })
+        .collect::<BooleanArray>();
+
+    let sample = arrow::compute::filter_record_batch(batch, &selection).unwrap();
+    Some(SampleBatch {
+        rate: sample.num_rows() as f64 / batch.num_rows() as f64,
+        population: batch.num_rows() as f64,
+        batch: sample,
+    })
+}
+
+// Queries with a single counting operation have an approximate answer,
+// derived metrics of it (see derived::split_metrics) are not scaled and are left out
+pub fn approximable(op_vec: &[&str]) -> bool {
+    match op_vec {
+        [op] => {
+            APPROXIMATE_OPERATIONS.contains(&op.split('?').next().unwrap_or(""))
+                && !op.contains(METRICS_SEPARATOR)
+        }
+        _ => false,
+    }
+}
+
+// Scales the count columns of a result on the sample to the whole profile and adds
+// <column>_low and <column>_high, the bounds of the 95% confidence interval.
+// The interval treats the sample as simple random sample, stratification only narrows it
+pub fn approximate_result(result: &RecordBatch, sample: &SampleBatch) -> RecordBatch {
+    let sample_size = sample.batch.num_rows() as f64;
+    let finite_correction = 1. - sample.rate;
+
+    let mut fields = Vec::new();
+    let mut columns: Vec<ArrayRef> = Vec::new();
+    for (i, field) in result.schema().fields().iter().enumerate() {
+        if !COUNT_COLUMNS.contains(&field.name().as_str()) || field.data_type() != &DataType::Float64 {
+            fields.push(field.to_owned());
+            columns.push(result.column(i).to_owned());
+            continue;
+        }
+
+        let column = get_floatarray_column(result, i);
+        let mut estimate_vec = Vec::new();
+        let mut low_vec = Vec::new();
+        let mut high_vec = Vec::new();
+        for count in column.values() {
+            let share = (count / sample_size).min(1.);
+            let variance = sample.population.powi(2) * finite_correction * share * (1. - share)
+                / (sample_size - 1.).max(1.);
+            let half_width = Z_95 * variance.sqrt();
+            let estimate = count / sample.rate;
+            estimate_vec.push(estimate.round());
+            low_vec.push((estimate - half_width).max(0.).round());
+            high_vec.push((estimate + half_width).round());
+        }
+
+        fields.push(field.to_owned());
+        columns.push(Arc::new(Float64Array::from(estimate_vec)));
+        fields.push(Field::new(&format!("{}_low", field.name()), DataType::Float64, false));
+        columns.push(Arc::new(Float64Array::from(low_vec)));
+        fields.push(Field::new(&format!("{}_high", field.name()), DataType::Float64, false));
+        columns.push(Arc::new(Float64Array::from(high_vec)));
+    }
+
+    let mut metadata = result.schema().metadata().to_owned();
+    metadata.insert("approximate".to_string(), "true".to_string());
+    metadata.insert("sample_rate".to_string(), sample.rate.to_string());
+    RecordBatch::try_new(Arc::new(Schema::new_with_metadata(fields, metadata)), columns).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_util::profile_batch;
+
+    #[test]
+    fn only_counting_operations_are_approximable() {
+        assert!(approximable(&["count?operator"]));
+        assert!(approximable(&["sunburst?pipeline,operator"]));
+        assert!(approximable(&["absfreq?operator,time:0.2"]));
+        assert!(!approximable(&["count(distinct)?operator"]));
+        assert!(!approximable(&["max(time)?time"]));
+        assert!(!approximable(&["relative?operator"]));
+        assert!(!approximable(&["count?operator", "sort?count,desc"]));
+        assert!(!approximable(&[]));
+    }
+
+    #[test]
+    fn sample_keeps_stratum_shares() {
+        let batch = profile_batch(SAMPLE_ROWS * 2);
+        let sample = build_sample(&batch).unwrap();
+        assert!((sample.rate - 0.5).abs() < 0.001);
+
+        let events = get_stringarray_column(&sample.batch, RecordBatchSchema::EvName as usize);
+        let cycles = (0..sample.batch.num_rows()).filter(|i| events.value(*i) == "cycles").count();
+        assert!((cycles as f64 / sample.batch.num_rows() as f64 - 0.5).abs() < 0.001);
+        assert!(build_sample(&profile_batch(SAMPLE_ROWS)).is_none());
+    }
+
+    #[test]
+    fn counts_are_scaled_with_interval() {
+        let batch = profile_batch(SAMPLE_ROWS * 2);
+        let sample = build_sample(&batch).unwrap();
+        let result = crate::utils::record_batch_util::create_new_record_batch(
+            vec!["operator", "count"],
+            vec![DataType::Utf8, DataType::Float64],
+            vec![
+                Arc::new(arrow::array::StringArray::from(vec!["tablescan1"])),
+                Arc::new(Float64Array::from(vec![1000.])),
+            ],
+        );
+        let approximate = approximate_result(&result, &sample);
+        let schema = approximate.schema();
+        let names = schema.fields().iter().map(|field| field.name().as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, vec!["operator", "count", "count_low", "count_high"]);
+
+        let estimate = get_floatarray_column(&approximate, 1).value(0);
+        let low = get_floatarray_column(&approximate, 2).value(0);
+        let high = get_floatarray_column(&approximate, 3).value(0);
+        assert_eq!(estimate, (1000. / sample.rate).round());
+        assert!(low < estimate && estimate < high);
+        assert_eq!(schema.metadata().get("approximate").map(|x| x.as_str()), Some("true"));
+    }
+}
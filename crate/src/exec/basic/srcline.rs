@@ -0,0 +1,204 @@
+use std::{collections::BTreeMap, collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Float64Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::{
+        basic::{uir::round, uir_cost::dump_line},
+        plan::plan::unique_events,
+    },
+    state::state::get_serde_dict,
+    utils::{
+        array_util::{get_int64_column, get_stringarray_column},
+        record_batch_schema::RecordBatchSchema,
+        record_batch_util::{create_new_record_batch, numbered_column_names, push_float_columns},
+    },
+    web_file::{serde_reader::DictFields, uir_program::UirProgram},
+};
+
+pub static UNKNOWN: &str = "[unknown]";
+// File of srclines in the generated code, see dump_line
+static GENERATED: &str = "[generated]";
+
+// Source location of a srcline dictionary entry
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub function: String,
+    // generated, runtime or unknown
+    pub kind: &'static str,
+}
+
+fn is_path(token: &str) -> bool {
+    token.contains('/') || token.contains('.')
+}
+
+// Accepted formats: "dump:<uir line>" (see dump_line), "<file>:<line>", "<function> <file>:<line>" and "<symbol>".
+// Locations addr2line couldn't resolve ("??:0") are unknown.
+pub fn parse_srcline(srcline: &str, program: &UirProgram) -> SourceLocation {
+    if let Some(line) = dump_line(srcline) {
+        let function = line
+            .parse::<u64>()
+            .ok()
+            .and_then(|line| program.line_index.get(&line))
+            .and_then(|index| program.lines[*index].function)
+            .map(|function| program.functions[function].name.to_owned())
+            .unwrap_or(UNKNOWN.to_string());
+        return SourceLocation {
+            file: GENERATED.to_string(),
+            function: function,
+            kind: "generated",
+        };
+    }
+
+    // Strip the line number
+    let location = match srcline.rsplit_once(':') {
+        Some((location, line)) if line.chars().all(|c| c.is_ascii_digit()) => location,
+        _ => srcline,
+    }
+    .trim();
+
+    if location.is_empty() || location.starts_with("??") {
+        return SourceLocation {
+            file: UNKNOWN.to_string(),
+            function: UNKNOWN.to_string(),
+            kind: "unknown",
+        };
+    }
+
+    let (function, file) = match location.rsplit_once(char::is_whitespace) {
+        Some((function, file)) if is_path(file) => (function.trim(), file),
+        _ if is_path(location) && !location.contains("::") => (UNKNOWN, location),
+        _ => (location, UNKNOWN),
+    };
+    SourceLocation {
+        file: file.to_string(),
+        function: function.to_string(),
+        kind: "runtime",
+    }
+}
+
+// Samples of all srclines grouped by file or function.
+// perc{i}: share of all samples of the i-th event
+pub fn srcline(batch: &RecordBatch, group_by_function: bool) -> RecordBatch {
+    let event_column = get_stringarray_column(batch, RecordBatchSchema::EvName as usize);
+    let srcline_column = get_int64_column(batch, RecordBatchSchema::Uri as usize);
+    let dict = get_serde_dict().unwrap();
+    let srclines = dict.dict.get(&(DictFields::Srcline as i64)).unwrap();
+
+    let events = unique_events();
+    let event_index = events
+        .iter()
+        .enumerate()
+        .map(|(i, event)| (event.as_str(), i))
+        .collect::<HashMap<&str, usize>>();
+
+    // Every srcline is parsed once
+    let mut locations: HashMap<i64, SourceLocation> = HashMap::new();
+    let mut counts: BTreeMap<(String, &'static str), Vec<f64>> = BTreeMap::new();
+    let mut totals = vec![0.; events.len()];
+
+    for i in 0..batch.num_rows() {
+        let event = match event_index.get(event_column.value(i)) {
+            Some(event) => *event,
+            None => continue,
+        };
+        let key = srcline_column.value(i);
+        let location = locations.entry(key).or_insert_with(|| match srclines.get(&(key as u64)) {
+            Some(srcline) => parse_srcline(srcline, &dict.uir_program),
+            None => parse_srcline("", &dict.uir_program),
+        });
+        let group = if group_by_function {
+            location.function.to_owned()
+        } else {
+            location.file.to_owned()
+        };
+        counts
+            .entry((group, location.kind))
+            .or_insert(vec![0.; events.len()])[event] += 1.;
+        totals[event] += 1.;
+    }
+
+    let mut group_vec = Vec::new();
+    let mut kind_vec = Vec::new();
+    let mut count_vec = Vec::new();
+    let mut perc_vecs = vec![Vec::new(); events.len()];
+
+    for ((group, kind), count) in &counts {
+        group_vec.push(group.as_str());
+        kind_vec.push(*kind);
+        count_vec.push(count.iter().sum::<f64>());
+        for (i, perc_vec) in perc_vecs.iter_mut().enumerate() {
+            perc_vec.push(if totals[i] == 0. {
+                0.
+            } else {
+                round(count[i] / totals[i])
+            });
+        }
+    }
+
+    let perc_names = numbered_column_names("perc", events.len());
+
+    let mut field_names = vec![if group_by_function { "function" } else { "file" }, "kind", "count"];
+    let mut data_types = vec![DataType::Utf8, DataType::Utf8, DataType::Float64];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(group_vec)),
+        Arc::new(StringArray::from(kind_vec)),
+        Arc::new(Float64Array::from(count_vec)),
+    ];
+    push_float_columns(&perc_names, perc_vecs, &mut field_names, &mut data_types, &mut columns);
+
+    create_new_record_batch(field_names, data_types, columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web_file::serde_reader::DictionaryUri;
+
+    fn program() -> UirProgram {
+        let lines = ["define int32 @scan() [", "  return int32 0", "]"];
+        let uri_dict = lines
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let entry = DictionaryUri {
+                    pipeline: Some("pipeline1".to_string()),
+                    uir: Some(text.to_string()),
+                    instrid: None,
+                    op: None,
+                };
+                ((i + 1).to_string(), entry)
+            })
+            .collect::<HashMap<String, DictionaryUri>>();
+        UirProgram::parse(&uri_dict)
+    }
+
+    #[test]
+    fn srclines_are_parsed_into_locations() {
+        let program = program();
+        for (srcline, file, function, kind) in [
+            ("dump:2", "[generated]", "@scan", "generated"),
+            ("dump:99", "[generated]", "[unknown]", "generated"),
+            ("??:0", "[unknown]", "[unknown]", "unknown"),
+            ("??:?", "[unknown]", "[unknown]", "unknown"),
+            ("", "[unknown]", "[unknown]", "unknown"),
+            ("/home/ci/umbra/src/execution/HashJoin.cpp:211", "/home/ci/umbra/src/execution/HashJoin.cpp", "[unknown]", "runtime"),
+            ("umbra::HashJoin::probe /home/ci/umbra/src/execution/HashJoin.cpp:211", "/home/ci/umbra/src/execution/HashJoin.cpp", "umbra::HashJoin::probe", "runtime"),
+            ("std::vector<int>::push_back /usr/include/c++/11/bits/stl_vector.h:1198", "/usr/include/c++/11/bits/stl_vector.h", "std::vector<int>::push_back", "runtime"),
+            ("__memmove_avx_unaligned_erms", "[unknown]", "__memmove_avx_unaligned_erms", "runtime"),
+            ("umbra::Runtime::hash", "[unknown]", "umbra::Runtime::hash", "runtime"),
+        ] {
+            let location = SourceLocation {
+                file: file.to_string(),
+                function: function.to_string(),
+                kind: kind,
+            };
+            assert_eq!(parse_srcline(srcline, &program), location, "{}", srcline);
+        }
+    }
+}
@@ -0,0 +1,724 @@
+use std::{
+    collections::HashMap,
+    f64::{INFINITY, NEG_INFINITY},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Float64Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::{
+        basic::{derived::METRICS_SEPARATOR, op_mapping::init_mapping_operator},
+        freq::{
+            abs_freq::event_bucket,
+            freq::{create_freq_bucket, operator_bucket, Freq},
+        },
+        rest::{rest_api::split_query, rest_api_pars::freq_dimension},
+    },
+    state::state::{
+        get_mapping_operator, get_summary_cube, get_swimlane_record_batch,
+        get_unfiltered_record_batch, reset_swimlane_record_batch, set_swimlane_record_batch,
+    },
+    utils::{
+        array_util::{get_floatarray_column, get_stringarray_column},
+        record_batch_schema::RecordBatchSchema,
+        record_batch_util::create_new_record_batch,
+        string_util::{
+            split_at_colon, split_at_comma, split_at_excl_mark, split_at_question_mark, split_at_to,
+        },
+    },
+};
+
+// The profile is divided into at most this many base buckets
+static MAX_BASE_BUCKETS: f64 = 10_000.;
+// Finest base resolution, the smallest bucket size of the frontend
+static MIN_BASE_RESOLUTION: f64 = 0.01;
+// Dimensions of the cube and their columns
+static DIMENSIONS: [&str; 3] = ["ev_name", "pipeline", "operator"];
+static EVENT: usize = 0;
+static PIPELINE: usize = 1;
+static OPERATOR: usize = 2;
+
+// Ids of event, pipeline and operator
+type Cell = [u32; 3];
+
+// Values of a dimension and their ids
+#[derive(Default)]
+struct Dimension {
+    values: Vec<String>,
+    ids: HashMap<String, u32>,
+}
+
+impl Dimension {
+    fn id(&mut self, value: &str) -> u32 {
+        if let Some(id) = self.ids.get(value) {
+            return *id;
+        }
+        let id = self.values.len() as u32;
+        self.values.push(value.to_string());
+        self.ids.insert(value.to_string(), id);
+        id
+    }
+}
+
+// Samples per (base bucket, event, pipeline, operator) of the unfiltered batch.
+// Base bucket k holds the times (k * base, (k + 1) * base], the first one all earlier times as well
+pub struct SummaryCube {
+    base: f64,
+    // Rows of base bucket k in the unfiltered batch: row_start[k]..row_start[k + 1]
+    row_start: Vec<usize>,
+    cells: Vec<HashMap<Cell, f64>>,
+    dimensions: [Dimension; 3],
+}
+
+// Smallest of 1, 2, 5 * 10^n keeping the number of base buckets below the maximum
+fn base_resolution(duration: f64) -> f64 {
+    let raw = duration / MAX_BASE_BUCKETS;
+    if !(raw > MIN_BASE_RESOLUTION) {
+        return MIN_BASE_RESOLUTION;
+    }
+    let magnitude = 10f64.powf(raw.log10().floor());
+    [1., 2., 5., 10.]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= raw)
+        .unwrap_or(raw)
+}
+
+fn dimension_columns(batch: &RecordBatch) -> [&StringArray; 3] {
+    [
+        get_stringarray_column(batch, RecordBatchSchema::EvName as usize),
+        get_stringarray_column(batch, RecordBatchSchema::Pipeline as usize),
+        get_stringarray_column(batch, RecordBatchSchema::Operator as usize),
+    ]
+}
+
+// None if the samples are not ordered by time, roll-ups rely on it
+pub fn build_summary_cube(batch: &RecordBatch) -> Option<SummaryCube> {
+    let rows = batch.num_rows();
+    if rows == 0 {
+        return None;
+    }
+    let times = get_floatarray_column(batch, RecordBatchSchema::Time as usize).values();
+    if times.windows(2).any(|pair| pair[0] > pair[1]) {
+        return None;
+    }
+
+    let base = base_resolution(times[rows - 1]);
+    let columns = dimension_columns(batch);
+    let mut dimensions: [Dimension; 3] = Default::default();
+    let mut row_start = vec![0];
+    let mut cells = vec![HashMap::new()];
+    for (i, time) in times.iter().enumerate() {
+        // Upper bound of the current base bucket
+        while *time > cells.len() as f64 * base {
+            row_start.push(i);
+            cells.push(HashMap::new());
+        }
+        let cell = [
+            dimensions[EVENT].id(columns[EVENT].value(i)),
+            dimensions[PIPELINE].id(columns[PIPELINE].value(i)),
+            dimensions[OPERATOR].id(columns[OPERATOR].value(i)),
+        ];
+        *cells.last_mut().unwrap().entry(cell).or_insert(0.) += 1.;
+    }
+    row_start.push(rows);
+
+    Some(SummaryCube {
+        base: base,
+        row_start: row_start,
+        cells: cells,
+        dimensions: dimensions,
+    })
+}
+
+// Filters of a query: allowed ids per dimension and the time range, inclusive like filter_between
+struct CubeFilter {
+    allowed: [Vec<bool>; 3],
+    from: f64,
+    to: f64,
+}
+
+impl CubeFilter {
+    // None if a filter can't be answered by the cube
+    fn parse(cube: &SummaryCube, filter_vec: &[&str]) -> Option<CubeFilter> {
+        let mut filter = CubeFilter {
+            allowed: [
+                vec![true; cube.dimensions[EVENT].values.len()],
+                vec![true; cube.dimensions[PIPELINE].values.len()],
+                vec![true; cube.dimensions[OPERATOR].values.len()],
+            ],
+            from: NEG_INFINITY,
+            to: INFINITY,
+        };
+        for item in filter_vec {
+            let split = item.split_terminator("=").collect::<Vec<&str>>();
+            let column = split[0].replace("?", "");
+            let value = split.get(1)?.replace("\"", "");
+
+            if value.contains(&"from_to") {
+                if column != "time" {
+                    return None;
+                }
+                let range = split_at_to(&value);
+                let from = range.get(0)?.parse::<f64>().ok()?;
+                let to = range.get(1)?.parse::<f64>().ok()?;
+                if from < 0. && to < 0. {
+                    continue;
+                }
+                filter.from = filter.from.max(from);
+                filter.to = filter.to.min(to);
+            } else {
+                let d = DIMENSIONS.iter().position(|name| *name == column)?;
+                let values = split_at_comma(&value);
+                if values == ["All"] {
+                    continue;
+                }
+                // The default event depends on the rows left by the previous filters
+                if values == ["Default"] {
+                    return None;
+                }
+                for (id, value) in cube.dimensions[d].values.iter().enumerate() {
+                    filter.allowed[d][id] &= values.contains(&value.as_str());
+                }
+            }
+        }
+        Some(filter)
+    }
+
+    fn accepts(&self, cell: &Cell) -> bool {
+        (0..3).all(|d| self.allowed[d][cell[d] as usize])
+    }
+}
+
+impl SummaryCube {
+    fn lower(&self, k: usize) -> f64 {
+        if k == 0 {
+            NEG_INFINITY
+        } else {
+            k as f64 * self.base
+        }
+    }
+
+    fn upper(&self, k: usize) -> f64 {
+        (k + 1) as f64 * self.base
+    }
+
+    // Base buckets possibly holding times between from and to, one extra on both sides
+    fn bucket_range(&self, from: f64, to: f64) -> std::ops::RangeInclusive<usize> {
+        let last = self.cells.len() - 1;
+        let bucket = |time: f64| {
+            if time > 0. {
+                ((time / self.base).ceil() as usize).saturating_sub(1).min(last)
+            } else {
+                0
+            }
+        };
+        bucket(from).saturating_sub(1)..=(bucket(to) + 1).min(last)
+    }
+
+    fn cell_of_row(&self, columns: &[&StringArray; 3], i: usize) -> Cell {
+        [
+            self.dimensions[EVENT].ids[columns[EVENT].value(i)],
+            self.dimensions[PIPELINE].ids[columns[PIPELINE].value(i)],
+            self.dimensions[OPERATOR].ids[columns[OPERATOR].value(i)],
+        ]
+    }
+
+    // Samples with lo < time <= hi passing the filter, per cell.
+    // Base buckets inside the range are rolled up, the rows of partially covered ones are counted
+    fn count(&self, batch: &RecordBatch, filter: &CubeFilter, lo: f64, hi: f64) -> HashMap<Cell, f64> {
+        let times = get_floatarray_column(batch, RecordBatchSchema::Time as usize).values();
+        let columns = dimension_columns(batch);
+        let in_range = |time: f64| time > lo && time <= hi && time >= filter.from && time <= filter.to;
+
+        let mut counts = HashMap::new();
+        for k in self.bucket_range(lo.max(filter.from), hi.min(filter.to)) {
+            let (lower, upper) = (self.lower(k), self.upper(k));
+            if upper <= lo || upper < filter.from || lower >= hi || lower >= filter.to {
+                continue;
+            }
+            if lower >= lo && lower >= filter.from && upper <= hi && upper <= filter.to {
+                for (cell, count) in &self.cells[k] {
+                    if filter.accepts(cell) {
+                        *counts.entry(*cell).or_insert(0.) += count;
+                    }
+                }
+                continue;
+            }
+            for i in self.row_start[k]..self.row_start[k + 1] {
+                let cell = self.cell_of_row(&columns, i);
+                if in_range(times[i]) && filter.accepts(&cell) {
+                    *counts.entry(cell).or_insert(0.) += 1.;
+                }
+            }
+        }
+        counts
+    }
+
+    // Time of the last sample passing the filter
+    fn last_time(&self, batch: &RecordBatch, filter: &CubeFilter) -> Option<f64> {
+        let times = get_floatarray_column(batch, RecordBatchSchema::Time as usize).values();
+        let columns = dimension_columns(batch);
+        for k in self.bucket_range(filter.from, filter.to).rev() {
+            if !self.cells[k].keys().any(|cell| filter.accepts(cell)) {
+                continue;
+            }
+            for i in (self.row_start[k]..self.row_start[k + 1]).rev() {
+                let time = times[i];
+                if time >= filter.from && time <= filter.to && filter.accepts(&self.cell_of_row(&columns, i)) {
+                    return Some(time);
+                }
+            }
+        }
+        None
+    }
+
+    // Same result as count::group_by
+    fn group_count(&self, batch: &RecordBatch, filter: &CubeFilter, d: usize) -> RecordBatch {
+        let mut totals = vec![0.; self.dimensions[d].values.len()];
+        for (cell, count) in self.count(batch, filter, NEG_INFINITY, INFINITY) {
+            totals[cell[d] as usize] += count;
+        }
+        create_new_record_batch(
+            vec![DIMENSIONS[d], "count"],
+            vec![DataType::Utf8, DataType::Float64],
+            vec![
+                Arc::new(StringArray::from(
+                    self.dimensions[d].values.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+                )),
+                Arc::new(Float64Array::from(totals)),
+            ],
+        )
+    }
+
+    // Same result as count::groupby_two_cols
+    fn sunburst(&self, batch: &RecordBatch, filter: &CubeFilter) -> RecordBatch {
+        let mut hashmap: HashMap<u32, HashMap<u32, f64>> = HashMap::new();
+        for (cell, count) in self.count(batch, filter, NEG_INFINITY, INFINITY) {
+            *hashmap
+                .entry(cell[PIPELINE])
+                .or_insert(HashMap::new())
+                .entry(cell[OPERATOR])
+                .or_insert(0.) += count;
+        }
+
+        let pipelines = &self.dimensions[PIPELINE].values;
+        let operators = &self.dimensions[OPERATOR].values;
+        let mut pip_builder = Vec::new();
+        let mut op_builder = Vec::new();
+        let mut pipecount = Vec::new();
+        let mut opcount = Vec::new();
+
+        for (pipeline, inner) in hashmap {
+            let pipeline = pipelines[pipeline as usize].as_str();
+            let mut total = 0.;
+            for (operator, count) in inner {
+                pip_builder.push(pipeline);
+                op_builder.push(operators[operator as usize].as_str());
+                pipecount.push(0.);
+                opcount.push(count);
+                total += count;
+            }
+            pip_builder.push("inner");
+            op_builder.push(pipeline);
+            pipecount.push(total);
+            opcount.push(0.);
+        }
+
+        create_new_record_batch(
+            vec!["pipeline", "operator", "pipecount", "opcount"],
+            vec![
+                DataType::Utf8,
+                DataType::Utf8,
+                DataType::Float64,
+                DataType::Float64,
+            ],
+            vec![
+                Arc::new(StringArray::from(pip_builder)),
+                Arc::new(StringArray::from(op_builder)),
+                Arc::new(Float64Array::from(pipecount)),
+                Arc::new(Float64Array::from(opcount)),
+            ],
+        )
+    }
+
+    // Calls f with the upper bound and the samples of every bucket of bucket_size from start,
+    // the first bucket also holds earlier samples. Buckets are accumulated like in abs_freq
+    fn for_each_bucket<F>(&self, batch: &RecordBatch, filter: &CubeFilter, start: f64, bucket_size: f64, mut f: F)
+    where
+        F: FnMut(f64, HashMap<Cell, f64>),
+    {
+        let last = match self.last_time(batch, filter) {
+            Some(last) => last,
+            None => return,
+        };
+        let mut lo = NEG_INFINITY;
+        let mut time_bucket = start;
+        loop {
+            f(time_bucket, self.count(batch, filter, lo, time_bucket));
+            if time_bucket >= last {
+                break;
+            }
+            lo = time_bucket;
+            time_bucket += bucket_size;
+        }
+    }
+
+    // Same result as abs_freq::abs_freq_of_event
+    fn abs_freq_of_event(&self, batch: &RecordBatch, filter: &CubeFilter, bucket_size: f64) -> RecordBatch {
+        let mut result_time_bucket = Vec::new();
+        let mut result_freq = Vec::new();
+        self.for_each_bucket(batch, filter, bucket_size, bucket_size, |time_bucket, counts| {
+            result_time_bucket.push(event_bucket(time_bucket, bucket_size));
+            result_freq.push(counts.values().fold(0., |sum, count| sum + count));
+        });
+
+        create_new_record_batch(
+            vec!["bucket", "absfreq"],
+            vec![DataType::Float64, DataType::Float64],
+            vec![
+                Arc::new(Float64Array::from(result_time_bucket)),
+                Arc::new(Float64Array::from(result_freq)),
+            ],
+        )
+    }
+
+    // Same result as freq::freq_of_operators for the operator dimension
+    fn freq_of_operators(
+        &self,
+        batch: &RecordBatch,
+        filter: &CubeFilter,
+        freq_type: Freq,
+        bucket_size: f64,
+        pipelines: Vec<&str>,
+        operators: Vec<&str>,
+        from: f64,
+    ) -> RecordBatch {
+        if let Some(pre_calc_batch) = get_swimlane_record_batch() {
+            let batch = pre_calc_batch.batch.to_owned();
+            reset_swimlane_record_batch();
+            return batch;
+        }
+
+        let selected = |list: &Vec<&str>, value: &str| {
+            list.contains(&value) || list.len() == 0 || (list.len() == 1 && list[0] == "All")
+        };
+        let pipeline_selected = self.dimensions[PIPELINE]
+            .values
+            .iter()
+            .map(|pipeline| selected(&pipelines, pipeline))
+            .collect::<Vec<bool>>();
+        let operator_values = &self.dimensions[OPERATOR].values;
+        let operator_selected = operator_values
+            .iter()
+            .map(|operator| selected(&operators, operator))
+            .collect::<Vec<bool>>();
+
+        init_mapping_operator();
+        let mapping = get_mapping_operator();
+        let map = mapping.lock().unwrap();
+
+        let mut result_time_bucket = Vec::new();
+        let mut result_vec_operator = Vec::new();
+        let mut result_vec_operator_nice_format = Vec::new();
+        let mut result_abs_freq = Vec::new();
+        let mut result_rel_freq = Vec::new();
+
+        let start = if from == -1. { 0. + bucket_size } else { from + bucket_size };
+        self.for_each_bucket(batch, filter, start, bucket_size, |time_bucket, counts| {
+            let mut abs_freq = vec![0.; operator_values.len()];
+            let mut sum = 0.;
+            for (cell, count) in counts {
+                sum += count;
+                if pipeline_selected[cell[PIPELINE] as usize] && operator_selected[cell[OPERATOR] as usize] {
+                    abs_freq[cell[OPERATOR] as usize] += count;
+                }
+            }
+            for (operator, abs_freq) in operator_values.iter().zip(abs_freq) {
+                result_time_bucket.push(operator_bucket(time_bucket, bucket_size));
+                result_vec_operator.push(operator.as_str());
+                result_vec_operator_nice_format
+                    .push(map.get(operator).map(|x| x.as_str()).unwrap_or(operator));
+                result_abs_freq.push(abs_freq);
+                result_rel_freq.push(if abs_freq == 0. {
+                    0.
+                } else {
+                    f64::trunc(abs_freq / sum * 100.0) / 100.0
+                });
+            }
+        });
+
+        let column_for_operator = RecordBatchSchema::Operator as usize;
+        if matches!(freq_type, Freq::REL) {
+            set_swimlane_record_batch(create_freq_bucket(
+                batch,
+                column_for_operator,
+                result_time_bucket.to_owned(),
+                result_vec_operator.to_owned(),
+                result_vec_operator_nice_format.to_owned(),
+                result_abs_freq.to_owned(),
+                Freq::ABS,
+            ));
+        }
+
+        create_freq_bucket(
+            batch,
+            column_for_operator,
+            result_time_bucket,
+            result_vec_operator,
+            result_vec_operator_nice_format,
+            if matches!(freq_type, Freq::ABS) {
+                result_abs_freq
+            } else {
+                result_rel_freq
+            },
+            freq_type,
+        )
+    }
+
+    // absfreq and relfreq like abs_freq_pars and rel_freq_pars, None for other dimensions
+    // and for the frequency of two events
+    fn freq(&self, batch: &RecordBatch, filter: &CubeFilter, freq_type: Freq, params: &str) -> Option<RecordBatch> {
+        let split_fields_bucket_size = split_at_colon(params);
+        let fields = split_at_comma(split_fields_bucket_size[0]);
+        if params.contains("&") || freq_dimension(fields.get(0).copied().unwrap_or("")) != "operator" {
+            return None;
+        }
+
+        if matches!(freq_type, Freq::ABS) && !split_fields_bucket_size[0].contains("pipeline") {
+            let bucket_size = split_fields_bucket_size.get(1)?.parse::<f64>().ok()?;
+            if !(bucket_size > 0.) {
+                return None;
+            }
+            return Some(self.abs_freq_of_event(batch, filter, bucket_size));
+        }
+
+        let split = split_at_excl_mark(params);
+        if split.len() < 4 || (matches!(freq_type, Freq::REL) && fields.get(1) != Some(&"time")) {
+            return None;
+        }
+        let bucket_size = split_at_colon(split[0]).get(1)?.parse::<f64>().ok()?;
+        let from = split_at_to(split[3]).get(0)?.parse::<f64>().ok()?;
+        if !(bucket_size > 0.) {
+            return None;
+        }
+        Some(self.freq_of_operators(
+            batch,
+            filter,
+            freq_type,
+            bucket_size,
+            split_at_comma(split[1]),
+            split_at_comma(split[2]),
+            from,
+        ))
+    }
+}
+
+// Result of a query with one count, sunburst, absfreq or relfreq operation, rolled up from the cube.
+// None if the cube can't answer it (other filters or operations, no cube), then the samples are scanned
+pub fn answer_from_cube(restful_string: &str) -> Option<RecordBatch> {
+    let cube = get_summary_cube()?;
+    let unfiltered = get_unfiltered_record_batch()?;
+    let batch = &unfiltered.batch;
+
+    let (filter_vec, op_vec, _) = split_query(restful_string);
+    if op_vec.len() != 1 {
+        return None;
+    }
+    let filter = CubeFilter::parse(&cube, &filter_vec)?;
+
+    let split = split_at_question_mark(op_vec[0]);
+    let params = split.get(1).copied().unwrap_or("");
+    // Derived metrics need the event counts, which the cube does not hold
+    if params.contains(METRICS_SEPARATOR) {
+        return None;
+    }
+    match split[0] {
+        "count" => {
+            let d = DIMENSIONS.iter().position(|name| *name == params)?;
+            Some(cube.group_count(batch, &filter, d))
+        }
+        "sunburst" => Some(cube.sunburst(batch, &filter)),
+        "absfreq" => cube.freq(batch, &filter, Freq::ABS, params),
+        "relfreq" => cube.freq(batch, &filter, Freq::REL, params),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::util::display::array_value_to_string;
+
+    use super::*;
+    use crate::{
+        exec::rest::rest_api::{eval_filters, eval_operations},
+        state::state::{set_summary_cube, set_unfiltered_record_batch},
+        utils::test_util::profile_batch,
+    };
+
+    static OPERATORS: [&str; 4] = ["tablescan1", "hashjoin2", "groupby3", "No operator"];
+
+    fn load_profile(rows: usize) -> RecordBatch {
+        let batch = profile_batch(rows);
+        set_summary_cube(build_summary_cube(&batch));
+        set_unfiltered_record_batch(batch.clone());
+        batch
+    }
+
+    // Result of the query without the cube: filters and operation on the samples
+    fn scanned(query: &str) -> RecordBatch {
+        let (filter_vec, op_vec, _) = split_query(query);
+        let batch = eval_filters(get_unfiltered_record_batch().unwrap().batch.clone(), filter_vec);
+        eval_operations(batch, op_vec).unwrap()
+    }
+
+    // Rows as text, grouped results are not ordered
+    fn sorted_rows(batch: &RecordBatch) -> Vec<String> {
+        let mut rows = (0..batch.num_rows())
+            .map(|i| {
+                batch
+                    .columns()
+                    .iter()
+                    .map(|column| array_value_to_string(column, i).unwrap())
+                    .collect::<Vec<String>>()
+                    .join("|")
+            })
+            .collect::<Vec<String>>();
+        rows.sort();
+        rows
+    }
+
+    fn assert_same_result(query: &str) {
+        let cube = answer_from_cube(query).expect(query);
+        // relfreq leaves its absolute frequencies for the next swimlane query
+        reset_swimlane_record_batch();
+        let scan = scanned(query);
+        reset_swimlane_record_batch();
+        assert_eq!(cube.schema(), scan.schema(), "{}", query);
+        assert_eq!(sorted_rows(&cube), sorted_rows(&scan), "{}", query);
+    }
+
+    // Ranges starting and ending inside base buckets, on their bounds and on sample times
+    fn time_ranges(batch: &RecordBatch) -> Vec<String> {
+        let times = get_floatarray_column(batch, RecordBatchSchema::Time as usize);
+        let mut ranges = vec![
+            "-1from_to-1".to_string(),
+            "0from_to1".to_string(),
+            "0.5from_to2.5".to_string(),
+            "0.503from_to0.997".to_string(),
+            "0.07from_to0.07".to_string(),
+            "3from_to100".to_string(),
+        ];
+        for (from, to) in [(10, 500), (13, 17), (200, 200), (0, 999)] {
+            ranges.push(format!("{}from_to{}", times.value(from), times.value(to)));
+        }
+        ranges
+    }
+
+    #[test]
+    fn counts_equal_the_scanned_samples() {
+        let batch = load_profile(1000);
+        let mut filters = vec![
+            String::new(),
+            "/?ev_name=\"cycles\"".to_string(),
+            "/?ev_name=\"All\"/?operator=\"tablescan1,groupby3\"".to_string(),
+            "/?pipeline=\"pipeline2\"/?operator=\"All\"".to_string(),
+        ];
+        for range in time_ranges(&batch) {
+            filters.push(format!("/?ev_name=\"loads\"/?time=\"{}\"", range));
+        }
+
+        for filter in &filters {
+            for op in [
+                "count?operator",
+                "count?ev_name",
+                "count?pipeline",
+                "sunburst?pipeline",
+            ] {
+                assert_same_result(&format!("{}/{}", filter, op));
+            }
+        }
+    }
+
+    #[test]
+    fn frequencies_equal_the_scanned_samples() {
+        let batch = load_profile(1000);
+        for bucket_size in ["0.01", "0.05", "0.3", "1"] {
+            let query = format!("?ev_name=\"cycles\"/absfreq?ev_name,time:{}", bucket_size);
+            assert_same_result(&query);
+
+            for range in time_ranges(&batch) {
+                for (pipelines, operators) in [
+                    ("All", "All"),
+                    ("pipeline1,pipeline3", "All"),
+                    ("All", "hashjoin2,No operator"),
+                ] {
+                    for freq in ["absfreq", "relfreq"] {
+                        assert_same_result(&format!(
+                            "?ev_name=\"cycles\"/?time=\"{range}\"/{freq}?pipeline,time:{bucket_size}!{pipelines}!{operators}!{range}",
+                            range = range,
+                            freq = freq,
+                            bucket_size = bucket_size,
+                            pipelines = pipelines,
+                            operators = operators,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn other_queries_are_scanned() {
+        load_profile(100);
+        for query in [
+            "?ev_name=\"Default\"/count?operator",
+            "?uri=\"x\"/count?operator",
+            "?operator=\"x\"/?time=\"0from_to1\"/count?operator/count?pipeline",
+            "count?uri",
+            "count?operator;(cycles)",
+            "absfreq?tid,time:0.1!All!All!0from_to1",
+            "relfreq?pipeline,time:0.1!All&cycles,loads&All&0from_to1",
+        ] {
+            assert!(answer_from_cube(query).is_none(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn filters_select_cube_values() {
+        let batch = load_profile(100);
+        let cube = build_summary_cube(&batch).unwrap();
+        let filter_vec = [
+            "?ev_name=\"cycles\"",
+            "?operator=\"All\"",
+            "?time=\"-1from_to-1\"",
+            "?time=\"0.1from_to0.3\"",
+        ];
+        let filter = CubeFilter::parse(&cube, &filter_vec).unwrap();
+        assert_eq!((filter.from, filter.to), (0.1, 0.3));
+        for (d, allowed) in [(EVENT, vec!["cycles"]), (OPERATOR, OPERATORS.to_vec())] {
+            for (id, value) in cube.dimensions[d].values.iter().enumerate() {
+                let expected = allowed.contains(&value.as_str());
+                assert_eq!(filter.allowed[d][id], expected, "{}", value);
+            }
+        }
+
+        // Unknown values select no samples
+        let filter = CubeFilter::parse(&cube, &["?pipeline=\"pipeline9\""]).unwrap();
+        assert!(filter.allowed[PIPELINE].iter().all(|allowed| !allowed));
+        // Default events, other columns and invalid ranges are left to the filters
+        for filter in [
+            "?ev_name=\"Default\"",
+            "?uri=\"x\"",
+            "?ev_name=\"0from_to1\"",
+            "?time=\"afrom_to1\"",
+        ] {
+            assert!(CubeFilter::parse(&cube, &[filter]).is_none(), "{}", filter);
+        }
+    }
+}
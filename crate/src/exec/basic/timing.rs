@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use arrow::{
+    array::{ArrayRef, Float64Array, StringArray},
+    compute::{sort, subtract},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::freq::freq::round,
+    utils::{
+        array_util::{get_floatarray_column, get_stringarray_column},
+        record_batch_util::create_new_record_batch,
+    },
+};
+
+use super::groupby::{group_ranges, sort_by_columns};
+
+// Nearest-rank percentile of a sorted array
+fn percentile(sorted: &Float64Array, p: f64) -> f64 {
+    if sorted.len() == 0 {
+        return 0.;
+    }
+    let rank = (p / 100. * sorted.len() as f64).ceil() as usize;
+    sorted.value(rank.max(1) - 1)
+}
+
+// Gaps between consecutive samples of a group: time[i + 1] - time[i]
+fn sorted_gaps(time: &ArrayRef) -> Float64Array {
+    if time.len() < 2 {
+        return Float64Array::from(Vec::<f64>::new());
+    }
+    let next = time.slice(1, time.len() - 1);
+    let prev = time.slice(0, time.len() - 1);
+    let gaps = subtract(
+        next.as_any().downcast_ref::<Float64Array>().unwrap(),
+        prev.as_any().downcast_ref::<Float64Array>().unwrap(),
+    )
+    .unwrap();
+    let gaps: ArrayRef = Arc::new(gaps);
+    let sorted = sort(&gaps, None).unwrap();
+    Float64Array::from(sorted.data().clone())
+}
+
+// Timing distribution per group (operator, pipeline, ...)
+// first/last sample, active duration, share of all samples and gap percentiles
+pub fn timing_statistics(
+    batch: &RecordBatch,
+    column_for_group: usize,
+    column_for_time: usize,
+) -> RecordBatch {
+    let sorted_batch = sort_by_columns(batch, vec![column_for_group, column_for_time]);
+    let group_column = get_stringarray_column(&sorted_batch, column_for_group);
+    let time_column = get_floatarray_column(&sorted_batch, column_for_time);
+    let total = sorted_batch.num_rows() as f64;
+
+    let mut group_vec = Vec::new();
+    let mut count_vec = Vec::new();
+    let mut first_vec = Vec::new();
+    let mut last_vec = Vec::new();
+    let mut duration_vec = Vec::new();
+    let mut share_vec = Vec::new();
+    let mut p50_vec = Vec::new();
+    let mut p90_vec = Vec::new();
+    let mut p99_vec = Vec::new();
+
+    for (offset, length) in group_ranges(&sorted_batch, column_for_group) {
+        let first = time_column.value(offset);
+        let last = time_column.value(offset + length - 1);
+        let gaps = sorted_gaps(&sorted_batch.column(column_for_time).slice(offset, length));
+
+        group_vec.push(group_column.value(offset));
+        count_vec.push(length as f64);
+        first_vec.push(first);
+        last_vec.push(last);
+        duration_vec.push(last - first);
+        share_vec.push(round(length as f64 / total));
+        p50_vec.push(percentile(&gaps, 50.));
+        p90_vec.push(percentile(&gaps, 90.));
+        p99_vec.push(percentile(&gaps, 99.));
+    }
+
+    let schema = batch.schema();
+    let group_name = schema.field(column_for_group).name();
+
+    create_new_record_batch(
+        vec![
+            group_name,
+            "count",
+            "first",
+            "last",
+            "duration",
+            "time_share",
+            "gap_p50",
+            "gap_p90",
+            "gap_p99",
+        ],
+        vec![
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+        ],
+        vec![
+            Arc::new(StringArray::from(group_vec)),
+            Arc::new(Float64Array::from(count_vec)),
+            Arc::new(Float64Array::from(first_vec)),
+            Arc::new(Float64Array::from(last_vec)),
+            Arc::new(Float64Array::from(duration_vec)),
+            Arc::new(Float64Array::from(share_vec)),
+            Arc::new(Float64Array::from(p50_vec)),
+            Arc::new(Float64Array::from(p90_vec)),
+            Arc::new(Float64Array::from(p99_vec)),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::{Array, UInt32Array},
+        compute::take,
+    };
+
+    use super::*;
+    use crate::{
+        exec::basic::{basic::find_unique_string, basic::sort_batch, filter::filter_with},
+        utils::test_util::profile_batch,
+    };
+
+    // Profile batch with its rows out of time order
+    fn shuffled_batch(rows: usize) -> RecordBatch {
+        let batch = profile_batch(rows);
+        let indices = UInt32Array::from((0..rows).map(|i| ((i * 7) % rows) as u32).collect::<Vec<u32>>());
+        let columns = batch
+            .columns()
+            .iter()
+            .map(|column| take(column.as_ref(), &indices, None).unwrap())
+            .collect::<Vec<ArrayRef>>();
+        RecordBatch::try_new(batch.schema(), columns).unwrap()
+    }
+
+    #[test]
+    fn timing_statistics_equal_per_group_filtering() {
+        let batch = shuffled_batch(500);
+        let total = batch.num_rows() as f64;
+
+        // operator and pipeline
+        for column_for_group in [0, 3].iter().copied() {
+            let result = timing_statistics(&batch, column_for_group, 2);
+            let groups = sort_batch(&find_unique_string(&batch, column_for_group), 0, false);
+            let groups = get_stringarray_column(&groups, 0);
+            assert_eq!(result.num_rows(), groups.len());
+
+            for row in 0..groups.len() {
+                let group = groups.value(row);
+                let filtered = sort_batch(&filter_with(column_for_group, vec![group], &batch), 2, false);
+                let time = get_floatarray_column(&filtered, 2).values();
+                let first = time[0];
+                let last = time[time.len() - 1];
+                let gaps = time.windows(2).map(|pair| pair[1] - pair[0]).collect::<Vec<f64>>();
+                let gaps = sort(&(Arc::new(Float64Array::from(gaps)) as ArrayRef), None).unwrap();
+                let gaps = Float64Array::from(gaps.data().clone());
+
+                assert_eq!(get_stringarray_column(&result, 0).value(row), group);
+                let expected = vec![
+                    time.len() as f64,
+                    first,
+                    last,
+                    last - first,
+                    round(time.len() as f64 / total),
+                    percentile(&gaps, 50.),
+                    percentile(&gaps, 90.),
+                    percentile(&gaps, 99.),
+                ];
+                let actual = (1..result.num_columns())
+                    .map(|column| get_floatarray_column(&result, column).value(row))
+                    .collect::<Vec<f64>>();
+                assert_eq!(actual, expected, "{}", group);
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Float64Array, Int32Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::{basic::uir::round, plan::plan::unique_events},
+    state::state::get_serde_dict,
+    utils::{
+        array_util::{get_int64_column, get_stringarray_column},
+        record_batch_schema::RecordBatchSchema,
+        record_batch_util::{create_new_record_batch, numbered_column_names, push_float_columns},
+    },
+    web_file::{serde_reader::DictFields, uir_program::UirProgram},
+};
+
+// Line of a srcline in the generated code, "dump<file>:<line>", e.g. dump:12 or dump.uir:12
+pub fn dump_line(srcline: &str) -> Option<&str> {
+    if srcline.starts_with("dump") {
+        srcline.split_once(':').map(|(_, line)| line)
+    } else {
+        None
+    }
+}
+
+// Samples per uir.json line and event, only srclines of the generated code (see dump_line) count
+pub struct LineCounts {
+    pub events: Vec<String>,
+    pub counts: HashMap<u64, Vec<f64>>,
+    pub totals: Vec<f64>,
+}
+
+impl LineCounts {
+    pub fn of(&self, line: u64) -> Vec<f64> {
+        self.counts
+            .get(&line)
+            .cloned()
+            .unwrap_or(vec![0.; self.events.len()])
+    }
+
+    // Share of the samples of every event
+    pub fn shares(&self, counts: &[f64]) -> Vec<f64> {
+        counts
+            .iter()
+            .zip(&self.totals)
+            .map(|(count, total)| if *total == 0. { 0. } else { round(count / total) })
+            .collect()
+    }
+}
+
+pub fn count_per_line(batch: &RecordBatch) -> LineCounts {
+    let event_column = get_stringarray_column(batch, RecordBatchSchema::EvName as usize);
+    let srcline_column = get_int64_column(batch, RecordBatchSchema::Uri as usize);
+    let dict = get_serde_dict().unwrap();
+    let srclines = dict.dict.get(&(DictFields::Srcline as i64)).unwrap();
+
+    let events = unique_events();
+    let event_index = events
+        .iter()
+        .enumerate()
+        .map(|(i, event)| (event.as_str(), i))
+        .collect::<HashMap<&str, usize>>();
+
+    let mut counts: HashMap<u64, Vec<f64>> = HashMap::new();
+    let mut totals = vec![0.; events.len()];
+    for i in 0..batch.num_rows() {
+        let line = srclines
+            .get(&(srcline_column.value(i) as u64))
+            .and_then(|srcline| dump_line(srcline))
+            .and_then(|line| line.parse::<u64>().ok());
+        if let (Some(line), Some(event)) = (line, event_index.get(event_column.value(i))) {
+            counts.entry(line).or_insert(vec![0.; events.len()])[*event] += 1.;
+            totals[*event] += 1.;
+        }
+    }
+
+    LineCounts {
+        events: events,
+        counts: counts,
+        totals: totals,
+    }
+}
+
+fn add_vec(sum: &mut Vec<f64>, values: &[f64]) {
+    for (sum, value) in sum.iter_mut().zip(values) {
+        *sum += value;
+    }
+}
+
+// Appends count and perc{i} columns
+fn create_cost_batch(
+    field_names: Vec<&str>,
+    mut data_types: Vec<DataType>,
+    mut columns: Vec<ArrayRef>,
+    counts: &LineCounts,
+    costs: Vec<Vec<f64>>,
+) -> RecordBatch {
+    let perc_names = numbered_column_names("perc", counts.events.len());
+
+    let mut field_names: Vec<&str> = field_names;
+    field_names.push("count");
+    data_types.push(DataType::Float64);
+    columns.push(Arc::new(Float64Array::from(
+        costs.iter().map(|cost| cost.iter().sum::<f64>()).collect::<Vec<f64>>(),
+    )));
+
+    let shares = costs
+        .iter()
+        .map(|cost| counts.shares(cost))
+        .collect::<Vec<Vec<f64>>>();
+    let perc = (0..perc_names.len())
+        .map(|i| shares.iter().map(|share| share[i]).collect::<Vec<f64>>())
+        .collect::<Vec<Vec<f64>>>();
+    push_float_columns(&perc_names, perc, &mut field_names, &mut data_types, &mut columns);
+
+    create_new_record_batch(field_names, data_types, columns)
+}
+
+fn block_costs(program: &UirProgram, counts: &LineCounts) -> Vec<Vec<f64>> {
+    program
+        .blocks
+        .iter()
+        .map(|block| {
+            let mut cost = vec![0.; counts.events.len()];
+            for instruction in &block.instructions {
+                add_vec(&mut cost, &counts.of(program.instructions[*instruction].line));
+            }
+            cost
+        })
+        .collect()
+}
+
+// Samples per instruction (instrId)
+pub fn uir_instructions(batch: &RecordBatch) -> RecordBatch {
+    let dict = get_serde_dict().unwrap();
+    let program = &dict.uir_program;
+    let counts = count_per_line(batch);
+
+    let mut function_vec = Vec::new();
+    let mut block_vec = Vec::new();
+    let mut instr_id_vec = Vec::new();
+    let mut line_vec = Vec::new();
+    let mut opcode_vec = Vec::new();
+    let mut uir_vec = Vec::new();
+    let mut op_vec = Vec::new();
+    let mut pipeline_vec = Vec::new();
+    let mut costs = Vec::new();
+
+    for line in &program.lines {
+        let instruction = match line.instruction {
+            Some(instruction) => &program.instructions[instruction],
+            None => continue,
+        };
+        function_vec.push(program.functions[instruction.function].name.as_str());
+        block_vec.push(program.blocks[instruction.block].label.as_str());
+        instr_id_vec.push(instruction.instr_id.as_str());
+        line_vec.push(line.line as i32);
+        opcode_vec.push(instruction.opcode.as_str());
+        uir_vec.push(line.text.trim());
+        op_vec.push(line.op.as_deref().unwrap_or("None"));
+        pipeline_vec.push(line.pipeline.as_deref().unwrap_or("None"));
+        costs.push(counts.of(line.line));
+    }
+
+    create_cost_batch(
+        vec![
+            "function", "block", "instr_id", "line", "opcode", "uir", "op", "pipe",
+        ],
+        vec![
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Int32,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+        ],
+        vec![
+            Arc::new(StringArray::from(function_vec)),
+            Arc::new(StringArray::from(block_vec)),
+            Arc::new(StringArray::from(instr_id_vec)),
+            Arc::new(Int32Array::from(line_vec)),
+            Arc::new(StringArray::from(opcode_vec)),
+            Arc::new(StringArray::from(uir_vec)),
+            Arc::new(StringArray::from(op_vec)),
+            Arc::new(StringArray::from(pipeline_vec)),
+        ],
+        &counts,
+        costs,
+    )
+}
+
+// Samples per basic block
+pub fn uir_blocks(batch: &RecordBatch) -> RecordBatch {
+    let dict = get_serde_dict().unwrap();
+    let program = &dict.uir_program;
+    let counts = count_per_line(batch);
+
+    let mut function_vec = Vec::new();
+    let mut block_vec = Vec::new();
+    let mut instructions_vec = Vec::new();
+    let mut successors_vec = Vec::new();
+
+    for block in &program.blocks {
+        function_vec.push(program.functions[block.function].name.as_str());
+        block_vec.push(block.label.as_str());
+        instructions_vec.push(block.instructions.len() as i32);
+        successors_vec.push(
+            block
+                .successors
+                .iter()
+                .map(|successor| program.blocks[*successor].label.as_str())
+                .collect::<Vec<&str>>()
+                .join(","),
+        );
+    }
+
+    create_cost_batch(
+        vec!["function", "block", "instructions", "successors"],
+        vec![DataType::Utf8, DataType::Utf8, DataType::Int32, DataType::Utf8],
+        vec![
+            Arc::new(StringArray::from(function_vec)),
+            Arc::new(StringArray::from(block_vec)),
+            Arc::new(Int32Array::from(instructions_vec)),
+            Arc::new(StringArray::from(successors_vec)),
+        ],
+        &counts,
+        block_costs(program, &counts),
+    )
+}
+
+// Samples per function
+pub fn uir_functions(batch: &RecordBatch) -> RecordBatch {
+    let dict = get_serde_dict().unwrap();
+    let program = &dict.uir_program;
+    let counts = count_per_line(batch);
+    let block_costs = block_costs(program, &counts);
+
+    let mut function_vec = Vec::new();
+    let mut line_vec = Vec::new();
+    let mut blocks_vec = Vec::new();
+    let mut instructions_vec = Vec::new();
+    let mut costs = Vec::new();
+
+    for function in &program.functions {
+        let mut cost = vec![0.; counts.events.len()];
+        let mut instructions = 0;
+        for block in &function.blocks {
+            add_vec(&mut cost, &block_costs[*block]);
+            instructions += program.blocks[*block].instructions.len();
+        }
+        function_vec.push(function.name.as_str());
+        line_vec.push(function.line as i32);
+        blocks_vec.push(function.blocks.len() as i32);
+        instructions_vec.push(instructions as i32);
+        costs.push(cost);
+    }
+
+    create_cost_batch(
+        vec!["function", "line", "blocks", "instructions"],
+        vec![DataType::Utf8, DataType::Int32, DataType::Int32, DataType::Int32],
+        vec![
+            Arc::new(StringArray::from(function_vec)),
+            Arc::new(Int32Array::from(line_vec)),
+            Arc::new(Int32Array::from(blocks_vec)),
+            Arc::new(Int32Array::from(instructions_vec)),
+        ],
+        &counts,
+        costs,
+    )
+}
+
+// Control flow graph edges with the samples of source and target block
+pub fn uir_cfg(batch: &RecordBatch) -> RecordBatch {
+    let dict = get_serde_dict().unwrap();
+    let program = &dict.uir_program;
+    let counts = count_per_line(batch);
+    let block_costs = block_costs(program, &counts);
+    let block_count = |block: usize| block_costs[block].iter().sum::<f64>();
+
+    let mut function_vec = Vec::new();
+    let mut from_vec = Vec::new();
+    let mut to_vec = Vec::new();
+    let mut from_count_vec = Vec::new();
+    let mut to_count_vec = Vec::new();
+    let mut back_edge_vec = Vec::new();
+
+    for block in &program.blocks {
+        for successor in &block.successors {
+            function_vec.push(program.functions[block.function].name.as_str());
+            from_vec.push(block.label.as_str());
+            to_vec.push(program.blocks[*successor].label.as_str());
+            from_count_vec.push(block_count(block.id));
+            to_count_vec.push(block_count(*successor));
+            // Jumps back to an earlier block close a loop
+            back_edge_vec.push((*successor <= block.id) as i32);
+        }
+    }
+
+    create_new_record_batch(
+        vec!["function", "from", "to", "from_count", "to_count", "back_edge"],
+        vec![
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Int32,
+        ],
+        vec![
+            Arc::new(StringArray::from(function_vec)),
+            Arc::new(StringArray::from(from_vec)),
+            Arc::new(StringArray::from(to_vec)),
+            Arc::new(Float64Array::from(from_count_vec)),
+            Arc::new(Float64Array::from(to_count_vec)),
+            Arc::new(Int32Array::from(back_edge_vec)),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_srclines_match_the_dump_prefix() {
+        assert_eq!(dump_line("dump:12"), Some("12"));
+        assert_eq!(dump_line("dump.uir:12"), Some("12"));
+        assert_eq!(dump_line("dump"), None);
+        assert_eq!(dump_line("/src/dump.cpp:3"), None);
+        assert_eq!(dump_line("??:0"), None);
+    }
+}
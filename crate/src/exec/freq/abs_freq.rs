@@ -17,6 +17,11 @@ use crate::{
 
 use super::freq;
 
+// Bucket value of an event frequency, time_bucket is the upper bound of the bucket
+pub fn event_bucket(time_bucket: f64, bucket_size: f64) -> f64 {
+    (f64::trunc(time_bucket * 100.0) / 100.0) - bucket_size
+}
+
 pub fn abs_freq_of_event(
     batch: &RecordBatch,
     event_col: usize,
@@ -36,7 +41,7 @@ pub fn abs_freq_of_event(
     for (i, time) in time_column.into_iter().enumerate() {
 
         while time_bucket < time.unwrap() {
-            result_time_bucket.push((f64::trunc(time_bucket * 100.0) / 100.0) - bucket_size);
+            result_time_bucket.push(event_bucket(time_bucket, bucket_size));
             let frequenzy = bucket_map.get("sum").unwrap();
             result_freq.push(frequenzy.to_owned());
             // Reset
@@ -47,7 +52,7 @@ pub fn abs_freq_of_event(
         bucket_map.insert("sum", bucket_map.get("sum").unwrap() + 1.0);
 
         if i == time_column.len() - 1 {
-            result_time_bucket.push((f64::trunc(time_bucket * 100.0) / 100.0) - bucket_size);
+            result_time_bucket.push(event_bucket(time_bucket, bucket_size));
             let frequenzy = bucket_map.get("sum").unwrap();
             result_freq.push(frequenzy.to_owned());
             
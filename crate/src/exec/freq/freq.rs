@@ -35,6 +35,27 @@ pub fn round(to_round: f64) -> f64 {
     return f64::trunc((to_round) * 100.0) / 100.0;
 }
 
+// Bucket value of an operator frequency, time_bucket is the upper bound of the bucket
+pub fn operator_bucket(time_bucket: f64, bucket_size: f64) -> f64 {
+    round(round(time_bucket) - bucket_size)
+}
+
+// Bucket index and upper bound of every sample like in freq_of_operators, the samples are
+// ordered by time. The first bucket ends at start, it holds all earlier samples as well
+pub fn time_buckets(time_column: &Float64Array, start: f64, bucket_size: f64) -> Vec<(usize, f64)> {
+    let mut bucket = 0;
+    let mut time_bucket = start;
+    let mut buckets = Vec::with_capacity(time_column.len());
+    for i in 0..time_column.len() {
+        while time_bucket < time_column.value(i) {
+            time_bucket += bucket_size;
+            bucket += 1;
+        }
+        buckets.push((bucket, time_bucket));
+    }
+    buckets
+}
+
 // Creates a record batch for the abs/rel frequency of an operator
 pub fn create_freq_bucket(
     record_batch: &RecordBatch,
@@ -148,6 +169,8 @@ pub fn freq_of_operators(
     let operator_column = get_stringarray_column(batch, column_for_operator);
     let time_column = get_floatarray_column(batch, column_for_time);
     let pipeline_column = get_stringarray_column(batch, RecordBatchSchema::Pipeline as usize);
+    // The selected operators refer to the operator column, also for the other dimensions
+    let selection_column = get_stringarray_column(batch, RecordBatchSchema::Operator as usize);
 
     // Time bucket starts at zero or from the time given by the query
     let mut time_bucket = if from == -1. {
@@ -167,10 +190,13 @@ pub fn freq_of_operators(
     init_mapping_operator();
     let mapping = get_mapping_operator();
     let map = mapping.lock().unwrap();
+    // Other dimensions (tid, cpu, dso) have no nice format
+    let nice_format = |operator| map.get(operator).map(|x| x.as_str()).unwrap_or(operator);
 
     for (i, time) in time_column.into_iter().enumerate() {
         let current_operator = operator_column.value(i);
         let current_pipeline = pipeline_column.value(i);
+        let selected_operator = selection_column.value(i);
         let current_time = time.unwrap();
 
         // While time_bucket is smaller than current time
@@ -181,9 +207,9 @@ pub fn freq_of_operators(
                 let abs_freq = bucket_map.get(operator).unwrap();
 
                 // Set data for one time bucket
-                result_time_bucket.push(round(round(time_bucket) - bucket_size));
+                result_time_bucket.push(operator_bucket(time_bucket, bucket_size));
                 result_vec_operator.push(operator);
-                result_vec_operator_nice_format.push(map.get(operator).unwrap());
+                result_vec_operator_nice_format.push(nice_format(operator));
                 result_abs_freq.push(abs_freq.to_owned());
 
                 // For relative freq
@@ -211,7 +237,7 @@ pub fn freq_of_operators(
             || pipelines.len() == 0
             || (pipelines.len() == 1 && pipelines[0] == "All"))
             && ((operators.len() == 1 && operators[0] == "All")
-                || operators.contains(&current_operator)
+                || operators.contains(&selected_operator)
                 || operators.len() == 0)
         {
             bucket_map.insert(
@@ -232,9 +258,9 @@ pub fn freq_of_operators(
                 let frequenzy = bucket_map.get(operator).unwrap();
 
                 // Set data for one time bucket
-                result_time_bucket.push(round(round(time_bucket) - bucket_size));
+                result_time_bucket.push(operator_bucket(time_bucket, bucket_size));
                 result_vec_operator.push(operator);
-                result_vec_operator_nice_format.push(map.get(operator).unwrap());
+                result_vec_operator_nice_format.push(nice_format(operator));
                 result_abs_freq.push(frequenzy.to_owned());
 
                 // For relative freq
@@ -251,11 +277,6 @@ pub fn freq_of_operators(
         }
     }
 
-    let result_vec_operator_nice_format: Vec<&str> = result_vec_operator_nice_format
-        .iter()
-        .map(AsRef::as_ref)
-        .collect();
-
     // The relative freq and absolute freq of operators are calculates quite the same,
     // therefore when the absolute is requested the data for the relative is cached
     if matches!(freq_type, Freq::REL) {
@@ -597,3 +618,53 @@ pub fn freq_of_memory(
         send_record_batch_to_js(batch);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        state::state::set_unfiltered_record_batch,
+        utils::test_util::profile_batch,
+    };
+    use arrow::datatypes::{Field, Schema};
+
+    // Profile with a tid column, threads alternate per sample
+    fn batch_with_tid() -> RecordBatch {
+        let batch = profile_batch(1000);
+        let tid = (0..batch.num_rows())
+            .map(|i| if i % 2 == 0 { "1" } else { "2" })
+            .collect::<Vec<&str>>();
+        let mut fields = batch.schema().fields().to_owned();
+        fields.push(Field::new("tid", DataType::Utf8, false));
+        let mut columns = batch.columns().to_owned();
+        columns.push(Arc::new(StringArray::from(tid)));
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+    }
+
+    #[test]
+    fn other_dimensions_select_by_operator() {
+        let batch = batch_with_tid();
+        set_unfiltered_record_batch(batch.clone());
+        let tid = batch.num_columns() - 1;
+        let result = freq_of_operators(
+            &batch,
+            Freq::ABS,
+            tid,
+            RecordBatchSchema::Time as usize,
+            0.5,
+            vec!["All"],
+            vec!["tablescan1"],
+            -1.,
+            -1.,
+        );
+
+        let operators = get_stringarray_column(&batch, RecordBatchSchema::Operator as usize);
+        let expected = (0..batch.num_rows())
+            .filter(|i| operators.value(*i) == "tablescan1")
+            .count() as f64;
+        let absfreq = get_floatarray_column(&result, 3);
+        assert_eq!(result.schema().field(2).name(), "tid");
+        assert!(expected > 0.);
+        assert_eq!(absfreq.values().iter().sum::<f64>(), expected);
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Float64Array, Int32Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::utils::{
+    array_util::{get_floatarray_column, get_stringarray_column},
+    record_batch_schema::RecordBatchSchema,
+    record_batch_util::create_new_record_batch,
+};
+
+use super::freq::{operator_bucket, round, time_buckets};
+
+// Operator and pipeline occurrences of one time bucket or one phase
+#[derive(Clone, Default)]
+struct Distribution<'a> {
+    operators: HashMap<&'a str, f64>,
+    pipelines: HashMap<&'a str, f64>,
+    sum: f64,
+}
+
+impl<'a> Distribution<'a> {
+    fn add(&mut self, other: &Distribution<'a>) {
+        for (operator, count) in &other.operators {
+            *self.operators.entry(operator).or_insert(0.) += count;
+        }
+        for (pipeline, count) in &other.pipelines {
+            *self.pipelines.entry(pipeline).or_insert(0.) += count;
+        }
+        self.sum += other.sum;
+    }
+
+    // Total variation distance of the relative operator frequencies, between 0 and 1
+    fn distance(&self, other: &Distribution<'a>) -> f64 {
+        if self.sum == 0. || other.sum == 0. {
+            return 0.;
+        }
+        let mut distance = 0.;
+        for (operator, count) in &self.operators {
+            let other_count = other.operators.get(operator).unwrap_or(&0.);
+            distance += (count / self.sum - other_count / other.sum).abs();
+        }
+        for (operator, count) in &other.operators {
+            if !self.operators.contains_key(operator) {
+                distance += count / other.sum;
+            }
+        }
+        distance / 2.
+    }
+
+    fn dominant_pipeline(&self) -> (&'a str, f64) {
+        let mut pipelines = self.pipelines.iter().collect::<Vec<_>>();
+        pipelines.sort_by(|a, b| b.1.partial_cmp(a.1).unwrap().then(a.0.cmp(b.0)));
+        match pipelines.first() {
+            Some((pipeline, count)) => (pipeline, *count / self.sum),
+            None => ("None", 0.),
+        }
+    }
+}
+
+struct Phase<'a> {
+    start: usize,
+    end: usize,
+    distribution: Distribution<'a>,
+}
+
+// Segments the time axis into phases dominated by one pipeline or operator mix.
+// A new phase starts as soon as the operator distribution of a bucket differs from the
+// distribution of the running phase by more than the threshold (total variation distance).
+// Phases shorter than min_buckets are merged into their predecessor.
+// The buckets are the ones of freq_of_operators, the samples are ordered by time
+pub fn phases(
+    batch: &RecordBatch,
+    column_for_time: usize,
+    bucket_size: f64,
+    threshold: f64,
+    min_buckets: usize,
+) -> RecordBatch {
+    let operator_column = get_stringarray_column(batch, RecordBatchSchema::Operator as usize);
+    let pipeline_column = get_stringarray_column(batch, RecordBatchSchema::Pipeline as usize);
+    let time_column = get_floatarray_column(batch, column_for_time);
+
+    // Per bucket distributions, the buckets of the swimlane chart
+    let mut buckets: BTreeMap<usize, Distribution> = BTreeMap::new();
+    let mut upper_bounds: HashMap<usize, f64> = HashMap::new();
+    for (i, (bucket, upper_bound)) in time_buckets(time_column, bucket_size, bucket_size)
+        .into_iter()
+        .enumerate()
+    {
+        upper_bounds.insert(bucket, upper_bound);
+        let distribution = buckets.entry(bucket).or_insert(Distribution::default());
+        *distribution
+            .operators
+            .entry(operator_column.value(i))
+            .or_insert(0.) += 1.;
+        *distribution
+            .pipelines
+            .entry(pipeline_column.value(i))
+            .or_insert(0.) += 1.;
+        distribution.sum += 1.;
+    }
+
+    // Change-point detection
+    let mut phases: Vec<Phase> = Vec::new();
+    for (bucket, distribution) in buckets {
+        if let Some(phase) = phases.last_mut() {
+            if phase.distribution.distance(&distribution) <= threshold {
+                phase.end = bucket;
+                phase.distribution.add(&distribution);
+                continue;
+            }
+        }
+        phases.push(Phase {
+            start: bucket,
+            end: bucket,
+            distribution: distribution,
+        });
+    }
+
+    // Merge short phases
+    let mut merged_phases: Vec<Phase> = Vec::new();
+    for phase in phases {
+        if let Some(previous) = merged_phases.last_mut() {
+            if phase.end - phase.start + 1 < min_buckets {
+                previous.end = phase.end;
+                previous.distribution.add(&phase.distribution);
+                continue;
+            }
+        }
+        merged_phases.push(phase);
+    }
+
+    let mut phase_vec = Vec::new();
+    let mut start_vec = Vec::new();
+    let mut end_vec = Vec::new();
+    let mut pipeline_vec = Vec::new();
+    let mut pipeline_share_vec = Vec::new();
+    let mut operator_vec = Vec::new();
+    let mut share_vec = Vec::new();
+
+    for (i, phase) in merged_phases.iter().enumerate() {
+        let (pipeline, pipeline_share) = phase.distribution.dominant_pipeline();
+        let mut operators = phase.distribution.operators.iter().collect::<Vec<_>>();
+        operators.sort_by(|a, b| b.1.partial_cmp(a.1).unwrap().then(a.0.cmp(b.0)));
+        for (operator, count) in operators {
+            phase_vec.push(i as i32);
+            start_vec.push(operator_bucket(upper_bounds[&phase.start], bucket_size));
+            end_vec.push(operator_bucket(upper_bounds[&phase.end] + bucket_size, bucket_size));
+            pipeline_vec.push(pipeline);
+            pipeline_share_vec.push(round(pipeline_share));
+            operator_vec.push(*operator);
+            share_vec.push(round(count / phase.distribution.sum));
+        }
+    }
+
+    create_new_record_batch(
+        vec![
+            "phase",
+            "start",
+            "end",
+            "pipeline",
+            "pipeline_share",
+            "operator",
+            "share",
+        ],
+        vec![
+            DataType::Int32,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Utf8,
+            DataType::Float64,
+        ],
+        vec![
+            Arc::new(Int32Array::from(phase_vec)),
+            Arc::new(Float64Array::from(start_vec)),
+            Arc::new(Float64Array::from(end_vec)),
+            Arc::new(StringArray::from(pipeline_vec)),
+            Arc::new(Float64Array::from(pipeline_share_vec)),
+            Arc::new(StringArray::from(operator_vec)),
+            Arc::new(Float64Array::from(share_vec)),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exec::{
+            freq::freq::{freq_of_operators, Freq},
+            rest::rest_api_pars::phases_pars,
+        },
+        state::state::set_unfiltered_record_batch,
+        utils::{array_util::get_int32_column, test_util::profile_batch},
+    };
+
+    static OPERATOR: usize = RecordBatchSchema::Operator as usize;
+    static TIME: usize = RecordBatchSchema::Time as usize;
+
+    // A sample every 0.01, tablescan1 in pipeline1 before row change and hashjoin2 in pipeline2 after
+    fn two_phase_batch(rows: usize, change: usize) -> RecordBatch {
+        let batch = profile_batch(rows);
+        let first = |i: usize| i < change;
+        let mut columns = batch.columns().to_vec();
+        columns[OPERATOR] = Arc::new(StringArray::from(
+            (0..rows)
+                .map(|i| if first(i) { "tablescan1" } else { "hashjoin2" })
+                .collect::<Vec<&str>>(),
+        ));
+        columns[RecordBatchSchema::Pipeline as usize] = Arc::new(StringArray::from(
+            (0..rows)
+                .map(|i| if first(i) { "pipeline1" } else { "pipeline2" })
+                .collect::<Vec<&str>>(),
+        ));
+        columns[TIME] = Arc::new(Float64Array::from(
+            (1..=rows).map(|i| i as f64 / 100.).collect::<Vec<f64>>(),
+        ));
+        RecordBatch::try_new(batch.schema(), columns).unwrap()
+    }
+
+    #[test]
+    fn phases_start_at_the_swimlane_bucket_of_the_change() {
+        let batch = two_phase_batch(200, 132);
+        set_unfiltered_record_batch(batch.clone());
+        let result = phases(&batch, TIME, 0.1, 0.3, 2);
+        let phase = get_int32_column(&result, 0);
+        let start = get_floatarray_column(&result, 1);
+        let end = get_floatarray_column(&result, 2);
+        let pipeline = get_stringarray_column(&result, 3);
+        let operator = get_stringarray_column(&result, 5);
+        let rows = (0..result.num_rows())
+            .map(|i| (phase.value(i), pipeline.value(i), operator.value(i)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                (0, "pipeline1", "tablescan1"),
+                (1, "pipeline2", "hashjoin2"),
+                (1, "pipeline2", "tablescan1"),
+            ]
+        );
+
+        // Bucket values of the swimlane chart with hashjoin2 samples
+        let freq = freq_of_operators(
+            &batch,
+            Freq::ABS,
+            OPERATOR,
+            TIME,
+            0.1,
+            vec!["All"],
+            vec!["All"],
+            -1.,
+            -1.,
+        );
+        let bucket = get_floatarray_column(&freq, 0);
+        let freq_operator = get_stringarray_column(&freq, 2);
+        let absfreq = get_floatarray_column(&freq, 3);
+        let hashjoin_buckets = (0..freq.num_rows())
+            .filter(|i| freq_operator.value(*i) == "hashjoin2" && absfreq.value(*i) > 0.)
+            .map(|i| bucket.value(i))
+            .collect::<Vec<f64>>();
+        assert_eq!(start.values(), &[0., hashjoin_buckets[0], hashjoin_buckets[0]]);
+        assert_eq!(end.value(0), hashjoin_buckets[0]);
+        assert_eq!(end.value(1), round(hashjoin_buckets.last().unwrap() + 0.1));
+    }
+
+    #[test]
+    fn invalid_parameters_are_errors() {
+        let batch = two_phase_batch(20, 10);
+        for params in ["time:0", "time:x", "time", "time:0.1!2", "time:0.1!0.3,x"] {
+            let result = phases_pars(batch.clone(), params);
+            assert_eq!(result.schema().field(0).name(), "error", "{}", params);
+        }
+        let result = phases_pars(batch, "time:0.1!0.3,2");
+        assert_eq!(result.schema().field(0).name(), "phase");
+    }
+}
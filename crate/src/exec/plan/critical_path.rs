@@ -0,0 +1,354 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Float64Array, Int32Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::freq::freq::round,
+    state::state::get_serde_dict,
+    utils::{
+        array_util::{get_floatarray_column, get_stringarray_column},
+        record_batch_schema::RecordBatchSchema,
+        record_batch_util::create_new_record_batch,
+    },
+};
+
+// Active interval of a pipeline derived from its first and last sample
+#[derive(Clone, Debug)]
+pub struct PipelineInterval {
+    pub start: f64,
+    pub end: f64,
+    pub count: f64,
+}
+
+fn pipeline_intervals(batch: &RecordBatch) -> BTreeMap<&str, PipelineInterval> {
+    let pipeline_column = get_stringarray_column(batch, RecordBatchSchema::Pipeline as usize);
+    let time_column = get_floatarray_column(batch, RecordBatchSchema::Time as usize);
+
+    let mut intervals: BTreeMap<&str, PipelineInterval> = BTreeMap::new();
+    for i in 0..batch.num_rows() {
+        let time = time_column.value(i);
+        let interval = intervals
+            .entry(pipeline_column.value(i))
+            .or_insert(PipelineInterval {
+                start: time,
+                end: time,
+                count: 0.,
+            });
+        interval.start = interval.start.min(time);
+        interval.end = interval.end.max(time);
+        interval.count += 1.;
+    }
+    intervals
+}
+
+// Pipeline dependencies (pipeline => pipelines it waits for) from the query plan.
+// A child operator that runs in other pipelines than its parent is a pipeline breaker,
+// hence the pipelines of the parent depend on the pipelines of the child.
+fn pipeline_dependencies(batch: &RecordBatch) -> HashMap<String, BTreeSet<String>> {
+    let operator_column = get_stringarray_column(batch, RecordBatchSchema::Operator as usize);
+    let pipeline_column = get_stringarray_column(batch, RecordBatchSchema::Pipeline as usize);
+
+    let mut operator_pipelines: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for i in 0..batch.num_rows() {
+        operator_pipelines
+            .entry(operator_column.value(i))
+            .or_insert(BTreeSet::new())
+            .insert(pipeline_column.value(i));
+    }
+
+    let mut dependencies: HashMap<String, BTreeSet<String>> = HashMap::new();
+    let dict = get_serde_dict().unwrap();
+    if let Some(plan) = &dict.query_plan {
+        let empty = BTreeSet::new();
+        for (parent, child) in plan.edges() {
+            let parent_pipelines = operator_pipelines.get(parent).unwrap_or(&empty);
+            let child_pipelines = operator_pipelines.get(child).unwrap_or(&empty);
+            for parent_pipeline in parent_pipelines {
+                for child_pipeline in child_pipelines {
+                    if !parent_pipelines.contains(child_pipeline) {
+                        dependencies
+                            .entry(parent_pipeline.to_string())
+                            .or_insert(BTreeSet::new())
+                            .insert(child_pipeline.to_string());
+                    }
+                }
+            }
+        }
+    }
+    dependencies
+}
+
+// Walk back from the pipeline that ends last, always to the predecessor that finished last.
+// Predecessors are the plan dependencies, without any the pipelines which ended before the start.
+fn critical_path<'a>(
+    intervals: &BTreeMap<&'a str, PipelineInterval>,
+    dependencies: &HashMap<String, BTreeSet<String>>,
+) -> Vec<&'a str> {
+    let latest = |candidates: Vec<&'a str>| {
+        candidates.into_iter().max_by(|a, b| {
+            intervals[a]
+                .end
+                .partial_cmp(&intervals[b].end)
+                .unwrap()
+                .then(b.cmp(a))
+        })
+    };
+
+    let mut path = Vec::new();
+    let mut current = latest(intervals.keys().copied().collect());
+    while let Some(pipeline) = current {
+        path.push(pipeline);
+        let interval = &intervals[pipeline];
+        let candidates = if let Some(deps) = dependencies.get(pipeline) {
+            intervals
+                .keys()
+                .copied()
+                .filter(|p| deps.contains(*p) && !path.contains(p))
+                .collect::<Vec<&str>>()
+        } else {
+            intervals
+                .iter()
+                .filter(|(p, i)| i.end < interval.start && !path.contains(p))
+                .map(|(p, _)| *p)
+                .collect::<Vec<&str>>()
+        };
+        current = latest(candidates);
+    }
+    path.reverse();
+    path
+}
+
+// Gantt chart: active interval, overlap with all other pipelines and critical path flag per pipeline
+pub fn gantt(batch: &RecordBatch) -> RecordBatch {
+    let intervals = pipeline_intervals(batch);
+    let dependencies = pipeline_dependencies(batch);
+    let path = critical_path(&intervals, &dependencies);
+
+    let mut pipeline_vec = Vec::new();
+    let mut start_vec = Vec::new();
+    let mut end_vec = Vec::new();
+    let mut duration_vec = Vec::new();
+    let mut count_vec = Vec::new();
+    let mut overlap_vec = Vec::new();
+    let mut critical_vec = Vec::new();
+    let mut depends_on_vec = Vec::new();
+
+    for (pipeline, interval) in &intervals {
+        let mut overlap = 0.;
+        for (other, other_interval) in &intervals {
+            if other != pipeline {
+                let from = interval.start.max(other_interval.start);
+                let to = interval.end.min(other_interval.end);
+                overlap += (to - from).max(0.);
+            }
+        }
+
+        pipeline_vec.push(*pipeline);
+        start_vec.push(interval.start);
+        end_vec.push(interval.end);
+        duration_vec.push(round(interval.end - interval.start));
+        count_vec.push(interval.count);
+        overlap_vec.push(round(overlap));
+        critical_vec.push(path.contains(pipeline) as i32);
+        depends_on_vec.push(
+            dependencies
+                .get(*pipeline)
+                .map(|deps| deps.iter().cloned().collect::<Vec<String>>().join(","))
+                .unwrap_or(String::new()),
+        );
+    }
+
+    create_new_record_batch(
+        vec![
+            "pipeline",
+            "start",
+            "end",
+            "duration",
+            "count",
+            "overlap",
+            "critical",
+            "depends_on",
+        ],
+        vec![
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Int32,
+            DataType::Utf8,
+        ],
+        vec![
+            Arc::new(StringArray::from(pipeline_vec)),
+            Arc::new(Float64Array::from(start_vec)),
+            Arc::new(Float64Array::from(end_vec)),
+            Arc::new(Float64Array::from(duration_vec)),
+            Arc::new(Float64Array::from(count_vec)),
+            Arc::new(Float64Array::from(overlap_vec)),
+            Arc::new(Int32Array::from(critical_vec)),
+            Arc::new(StringArray::from(depends_on_vec)),
+        ],
+    )
+}
+
+// Pipelines on the critical path of the query's wall time in execution order
+// wait: time between the end of the predecessor and the start of the pipeline
+pub fn critical_path_list(batch: &RecordBatch) -> RecordBatch {
+    let intervals = pipeline_intervals(batch);
+    let dependencies = pipeline_dependencies(batch);
+    let path = critical_path(&intervals, &dependencies);
+
+    let mut position_vec = Vec::new();
+    let mut pipeline_vec = Vec::new();
+    let mut start_vec = Vec::new();
+    let mut end_vec = Vec::new();
+    let mut wait_vec = Vec::new();
+
+    let mut previous_end: Option<f64> = None;
+    for (i, pipeline) in path.into_iter().enumerate() {
+        let interval = &intervals[pipeline];
+        position_vec.push(i as i32);
+        pipeline_vec.push(pipeline);
+        start_vec.push(interval.start);
+        end_vec.push(interval.end);
+        wait_vec.push(round(
+            previous_end
+                .map(|end| (interval.start - end).max(0.))
+                .unwrap_or(interval.start),
+        ));
+        previous_end = Some(interval.end);
+    }
+
+    create_new_record_batch(
+        vec!["position", "pipeline", "start", "end", "wait"],
+        vec![
+            DataType::Int32,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+        ],
+        vec![
+            Arc::new(Int32Array::from(position_vec)),
+            Arc::new(StringArray::from(pipeline_vec)),
+            Arc::new(Float64Array::from(start_vec)),
+            Arc::new(Float64Array::from(end_vec)),
+            Arc::new(Float64Array::from(wait_vec)),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Array, Int64Array, UInt64Array};
+
+    use super::*;
+    use crate::{
+        state::state::set_serde_dict,
+        web_file::{query_plan::QueryPlan, serde_reader::SerdeDict},
+    };
+
+    // groupby3 runs in pipeline4 and waits for the join in pipeline3, which waits for the
+    // scans in pipeline1 and pipeline2. pipeline5 runs beside the scans outside of the plan.
+    fn load_profile() -> RecordBatch {
+        let plan = r#"{"operator": "groupby3", "input": {"operator": "hashjoin2",
+            "left": {"operator": "tablescan1"}, "right": {"operator": "tablescan4"}}}"#;
+        set_serde_dict(SerdeDict {
+            dict: HashMap::new(),
+            query_plan: QueryPlan::parse(plan),
+            uir_program: Default::default(),
+            mapping: HashMap::new(),
+            query_plan_json: plan.to_string(),
+        });
+
+        let samples = [
+            ("tablescan1", 0., "pipeline1"),
+            ("tablescan1", 1., "pipeline1"),
+            ("tablescan4", 0.5, "pipeline2"),
+            ("tablescan4", 3., "pipeline2"),
+            ("hashjoin2", 3.5, "pipeline3"),
+            ("hashjoin2", 5., "pipeline3"),
+            ("groupby3", 5.5, "pipeline4"),
+            ("groupby3", 6., "pipeline4"),
+            ("No operator", 0.25, "pipeline5"),
+            ("No operator", 2., "pipeline5"),
+        ];
+        let operator_vec = samples.iter().map(|sample| sample.0).collect::<Vec<&str>>();
+        let rows = samples.len();
+        create_new_record_batch(
+            vec![
+                "operator",
+                "ev_name",
+                "time",
+                "pipeline",
+                "addr",
+                "uri",
+                "op_ext",
+                "physical_op",
+            ],
+            vec![
+                DataType::Utf8,
+                DataType::Utf8,
+                DataType::Float64,
+                DataType::Utf8,
+                DataType::UInt64,
+                DataType::Int64,
+                DataType::Utf8,
+                DataType::Utf8,
+            ],
+            vec![
+                Arc::new(StringArray::from(operator_vec.clone())),
+                Arc::new(StringArray::from(vec!["cycles"; rows])),
+                Arc::new(Float64Array::from(samples.iter().map(|sample| sample.1).collect::<Vec<f64>>())),
+                Arc::new(StringArray::from(samples.iter().map(|sample| sample.2).collect::<Vec<&str>>())),
+                Arc::new(UInt64Array::from(vec![0; rows])),
+                Arc::new(Int64Array::from(vec![0; rows])),
+                Arc::new(StringArray::from(operator_vec.clone())),
+                Arc::new(StringArray::from(operator_vec)),
+            ],
+        )
+    }
+
+    fn strings(batch: &RecordBatch, column: usize) -> Vec<&str> {
+        let column = get_stringarray_column(batch, column);
+        (0..column.len()).map(|i| column.value(i)).collect()
+    }
+
+    fn floats(batch: &RecordBatch, column: usize) -> Vec<f64> {
+        get_floatarray_column(batch, column).values().to_vec()
+    }
+
+    #[test]
+    fn gantt_rows_of_the_plan() {
+        let gantt = gantt(&load_profile());
+        assert_eq!(
+            strings(&gantt, 0),
+            vec!["pipeline1", "pipeline2", "pipeline3", "pipeline4", "pipeline5"]
+        );
+        assert_eq!(floats(&gantt, 1), vec![0., 0.5, 3.5, 5.5, 0.25]);
+        assert_eq!(floats(&gantt, 2), vec![1., 3., 5., 6., 2.]);
+        assert_eq!(floats(&gantt, 3), vec![1., 2.5, 1.5, 0.5, 1.75]);
+        assert_eq!(floats(&gantt, 4), vec![2., 2., 2., 2., 2.]);
+        assert_eq!(floats(&gantt, 5), vec![1.25, 2., 0., 0., 2.25]);
+        let critical = gantt.column(6).as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(critical.values(), &[0, 1, 1, 1, 0]);
+        assert_eq!(strings(&gantt, 7), vec!["", "", "pipeline1,pipeline2", "pipeline3", ""]);
+    }
+
+    #[test]
+    fn critical_path_follows_the_latest_dependency() {
+        let path = critical_path_list(&load_profile());
+        assert_eq!(strings(&path, 1), vec!["pipeline2", "pipeline3", "pipeline4"]);
+        assert_eq!(floats(&path, 2), vec![0.5, 3.5, 5.5]);
+        assert_eq!(floats(&path, 3), vec![3., 5., 6.]);
+        assert_eq!(floats(&path, 4), vec![0.5, 0.5, 0.5]);
+    }
+}
@@ -0,0 +1,176 @@
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{Float64Array, Int32Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::freq::freq::round,
+    state::state::get_serde_dict,
+    utils::record_batch_util::create_new_record_batch,
+    web_file::query_plan::QueryPlan,
+};
+
+use super::plan::{count_per_operator_event, unique_events};
+
+// Operators are flagged if their observed share exceeds the estimate by more than this factor
+pub static DEFAULT_FACTOR: f64 = 2.;
+// Smallest estimated share, avoids divisions by zero for operators the optimizer considered free
+static MIN_SHARE: f64 = 0.001;
+
+// Estimated cost per plan node (same order as the nodes).
+// The optimizer's cost is cumulative, hence the exclusive cost is the difference to the children.
+// Without costs in the plan the estimated cardinality is used as proxy for the work of an operator.
+fn estimated_cost(plan: &QueryPlan) -> Vec<f64> {
+    let has_cost = plan.nodes.iter().any(|node| node.cost.is_some());
+
+    plan.nodes
+        .iter()
+        .map(|node| {
+            if has_cost {
+                let children_cost = node
+                    .children
+                    .iter()
+                    .map(|child| plan.nodes[*child].cost.unwrap_or(0.))
+                    .sum::<f64>();
+                (node.cost.unwrap_or(0.) - children_cost).max(0.)
+            } else {
+                node.cardinality.unwrap_or(0.)
+            }
+        })
+        .collect()
+}
+
+// Factor of misestimation?<factor>, DEFAULT_FACTOR if not given
+pub fn parse_factor(params: &str) -> Result<f64, String> {
+    let params = params.trim();
+    if params.is_empty() {
+        return Ok(DEFAULT_FACTOR);
+    }
+    match params.parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor > 0. => Ok(factor),
+        _ => Err(format!("Misestimation factor must be a positive number: {}", params)),
+    }
+}
+
+// Compares the estimated cost share of every plan operator with its observed sample share per event.
+// Rows are ranked by the misestimation ratio max(observed / estimated, estimated / observed),
+// flagged are operators whose observed share exceeds the estimate by more than the factor
+pub fn misestimation(batch: &RecordBatch, factor: f64) -> RecordBatch {
+    let dict = get_serde_dict().unwrap();
+    // Without a query plan the result is empty
+    let empty = QueryPlan::default();
+    let plan = dict.query_plan.as_ref().unwrap_or(&empty);
+
+    let events = unique_events();
+    let counts = count_per_operator_event(batch);
+
+    // Estimated shares per plan node
+    let estimated = estimated_cost(plan);
+    let estimated_total = estimated.iter().sum::<f64>();
+
+    // Observed samples of plan operators per event
+    let mut observed: HashMap<(usize, &str), f64> = HashMap::new();
+    let mut observed_total: HashMap<&str, f64> = HashMap::new();
+    for ((operator, event), count) in &counts {
+        if let Some(node) = plan.node_of_operator(operator) {
+            *observed.entry((node.id, event)).or_insert(0.) += count;
+            *observed_total.entry(event).or_insert(0.) += count;
+        }
+    }
+
+    let mut rows = Vec::new();
+    for event in &events {
+        let total = *observed_total.get(event.as_str()).unwrap_or(&0.);
+        if total == 0. {
+            continue;
+        }
+        for node in &plan.nodes {
+            let estimated_share = if estimated_total == 0. {
+                0.
+            } else {
+                estimated[node.id] / estimated_total
+            };
+            let observed_share =
+                observed.get(&(node.id, event.as_str())).unwrap_or(&0.) / total;
+            if estimated_share == 0. && observed_share == 0. {
+                continue;
+            }
+            let ratio = observed_share.max(MIN_SHARE) / estimated_share.max(MIN_SHARE);
+            let misestimation = ratio.max(1. / ratio);
+            rows.push((
+                node.operator.as_str(),
+                event.as_str(),
+                estimated_share,
+                observed_share,
+                ratio,
+                misestimation,
+            ));
+        }
+    }
+    rows.sort_by(|a, b| b.5.partial_cmp(&a.5).unwrap());
+
+    let mut rank_vec = Vec::new();
+    let mut operator_vec = Vec::new();
+    let mut event_vec = Vec::new();
+    let mut estimated_vec = Vec::new();
+    let mut observed_vec = Vec::new();
+    let mut ratio_vec = Vec::new();
+    let mut flag_vec = Vec::new();
+
+    for (rank, row) in rows.into_iter().enumerate() {
+        rank_vec.push(rank as i32 + 1);
+        operator_vec.push(row.0);
+        event_vec.push(row.1);
+        estimated_vec.push(round(row.2));
+        observed_vec.push(round(row.3));
+        ratio_vec.push(round(row.4));
+        flag_vec.push((row.3 > factor * row.2) as i32);
+    }
+
+    create_new_record_batch(
+        vec![
+            "rank",
+            "operator",
+            "ev_name",
+            "estimated_share",
+            "observed_share",
+            "ratio",
+            "flag",
+        ],
+        vec![
+            DataType::Int32,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Float64,
+            DataType::Int32,
+        ],
+        vec![
+            Arc::new(Int32Array::from(rank_vec)),
+            Arc::new(StringArray::from(operator_vec)),
+            Arc::new(StringArray::from(event_vec)),
+            Arc::new(Float64Array::from(estimated_vec)),
+            Arc::new(Float64Array::from(observed_vec)),
+            Arc::new(Float64Array::from(ratio_vec)),
+            Arc::new(Int32Array::from(flag_vec)),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn factor_defaults_and_rejects_invalid_input() {
+        assert_eq!(parse_factor(""), Ok(DEFAULT_FACTOR));
+        assert_eq!(parse_factor("1.5"), Ok(1.5));
+        for params in ["abc", "-2", "0", "inf", "NaN"] {
+            assert!(parse_factor(params).is_err(), "{}", params);
+        }
+    }
+}
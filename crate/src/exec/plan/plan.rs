@@ -0,0 +1,259 @@
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Float64Array, Int32Array, Int64Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::{basic::basic::find_unique_string, freq::freq::round},
+    state::state::{get_serde_dict, get_unfiltered_record_batch},
+    utils::{
+        array_util::get_stringarray_column, record_batch_schema::RecordBatchSchema,
+        record_batch_util::{create_new_record_batch, push_float_columns},
+    },
+    web_file::query_plan::QueryPlan,
+};
+
+// Sorted events of the profile, the i-th event belongs to the column perc{i+1}
+pub fn unique_events() -> Vec<String> {
+    let unique_events_batch = find_unique_string(
+        &get_unfiltered_record_batch().unwrap().batch,
+        RecordBatchSchema::EvName as usize,
+    );
+    let mut events = get_stringarray_column(&unique_events_batch, 0)
+        .into_iter()
+        .map(|event| event.unwrap().to_string())
+        .collect::<Vec<String>>();
+    events.sort();
+    events
+}
+
+// Samples per operator and event
+pub fn count_per_operator_event(batch: &RecordBatch) -> HashMap<(&str, &str), f64> {
+    let operator_column = get_stringarray_column(batch, RecordBatchSchema::Operator as usize);
+    let event_column = get_stringarray_column(batch, RecordBatchSchema::EvName as usize);
+
+    let mut counts = HashMap::new();
+    for i in 0..batch.num_rows() {
+        *counts
+            .entry((operator_column.value(i), event_column.value(i)))
+            .or_insert(0.) += 1.;
+    }
+    counts
+}
+
+// Plan nodes annotated with their samples
+// count: samples of the operator itself (exclusive), inclusive: samples of the operator and its subtree
+// perc_{event}: exclusive share of the operator of all samples of the event
+pub fn plan(batch: &RecordBatch) -> RecordBatch {
+    let dict = get_serde_dict().unwrap();
+    // Without a query plan the result is empty
+    let empty = QueryPlan::default();
+    let plan = dict.query_plan.as_ref().unwrap_or(&empty);
+
+    let events = unique_events();
+    let counts = count_per_operator_event(batch);
+
+    let mut event_totals = vec![0.; events.len()];
+    for ((_, event), count) in &counts {
+        if let Some(i) = events.iter().position(|e| e == event) {
+            event_totals[i] += count;
+        }
+    }
+    let total = batch.num_rows() as f64;
+
+    // Exclusive cost per node and event
+    let exclusive = plan
+        .nodes
+        .iter()
+        .map(|node| {
+            events
+                .iter()
+                .map(|event| {
+                    *counts
+                        .get(&(node.operator.as_str(), event.as_str()))
+                        .unwrap_or(&0.)
+                })
+                .collect::<Vec<f64>>()
+        })
+        .collect::<Vec<Vec<f64>>>();
+
+    // Inclusive cost: nodes are in pre-order, so children follow their parent
+    let mut inclusive = exclusive
+        .iter()
+        .map(|x| x.iter().sum::<f64>())
+        .collect::<Vec<f64>>();
+    for node in plan.nodes.iter().rev() {
+        if let Some(parent) = node.parent {
+            inclusive[parent] += inclusive[node.id];
+        }
+    }
+
+    let mut node_id_vec = Vec::new();
+    let mut parent_id_vec = Vec::new();
+    let mut analyze_plan_id_vec = Vec::new();
+    let mut operator_vec = Vec::new();
+    let mut child_type_vec = Vec::new();
+    let mut est_cardinality_vec = Vec::new();
+    let mut cardinality_vec = Vec::new();
+    let mut count_vec = Vec::new();
+    let mut inclusive_vec = Vec::new();
+    let mut exclusive_share_vec = Vec::new();
+    let mut inclusive_share_vec = Vec::new();
+    let mut perc_vecs = vec![Vec::new(); events.len()];
+
+    for node in &plan.nodes {
+        let count = exclusive[node.id].iter().sum::<f64>();
+        node_id_vec.push(node.id as i32);
+        parent_id_vec.push(node.parent.map(|x| x as i32).unwrap_or(-1));
+        analyze_plan_id_vec.push(node.analyze_plan_id.unwrap_or(-1));
+        operator_vec.push(node.operator.as_str());
+        child_type_vec.push(node.child_type.as_str());
+        est_cardinality_vec.push(node.cardinality.unwrap_or(-1.));
+        cardinality_vec.push(node.analyzed_cardinality.unwrap_or(-1.));
+        count_vec.push(count);
+        inclusive_vec.push(inclusive[node.id]);
+        exclusive_share_vec.push(if total == 0. { 0. } else { round(count / total) });
+        inclusive_share_vec.push(if total == 0. {
+            0.
+        } else {
+            round(inclusive[node.id] / total)
+        });
+        for (i, perc_vec) in perc_vecs.iter_mut().enumerate() {
+            perc_vec.push(if event_totals[i] == 0. {
+                0.
+            } else {
+                round(exclusive[node.id][i] / event_totals[i])
+            });
+        }
+    }
+
+    let perc_names = events
+        .iter()
+        .map(|event| format!("perc_{}", event))
+        .collect::<Vec<String>>();
+
+    let mut field_names = vec![
+        "node_id",
+        "parent_id",
+        "analyze_plan_id",
+        "operator",
+        "child_type",
+        "est_cardinality",
+        "cardinality",
+        "count",
+        "inclusive",
+        "exclusive_share",
+        "inclusive_share",
+    ];
+    let mut data_types = vec![
+        DataType::Int32,
+        DataType::Int32,
+        DataType::Int64,
+        DataType::Utf8,
+        DataType::Utf8,
+        DataType::Float64,
+        DataType::Float64,
+        DataType::Float64,
+        DataType::Float64,
+        DataType::Float64,
+        DataType::Float64,
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(node_id_vec)),
+        Arc::new(Int32Array::from(parent_id_vec)),
+        Arc::new(Int64Array::from(analyze_plan_id_vec)),
+        Arc::new(StringArray::from(operator_vec)),
+        Arc::new(StringArray::from(child_type_vec)),
+        Arc::new(Float64Array::from(est_cardinality_vec)),
+        Arc::new(Float64Array::from(cardinality_vec)),
+        Arc::new(Float64Array::from(count_vec)),
+        Arc::new(Float64Array::from(inclusive_vec)),
+        Arc::new(Float64Array::from(exclusive_share_vec)),
+        Arc::new(Float64Array::from(inclusive_share_vec)),
+    ];
+    push_float_columns(&perc_names, perc_vecs, &mut field_names, &mut data_types, &mut columns);
+
+    create_new_record_batch(field_names, data_types, columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        state::state::{set_serde_dict, set_unfiltered_record_batch},
+        utils::{array_util::get_floatarray_column, test_util::profile_batch},
+        web_file::serde_reader::SerdeDict,
+    };
+
+    fn load_profile() -> RecordBatch {
+        let batch = profile_batch(200);
+        set_unfiltered_record_batch(batch.clone());
+        let plan = r#"{"operator": "groupby3", "cardinality": 10, "input": {"operator": "hashjoin2",
+            "left": {"operator": "tablescan1"}, "right": {"operator": "tablescan4"}}}"#;
+        set_serde_dict(SerdeDict {
+            dict: HashMap::new(),
+            query_plan: QueryPlan::parse(plan),
+            uir_program: Default::default(),
+            mapping: HashMap::new(),
+            query_plan_json: plan.to_string(),
+        });
+        batch
+    }
+
+    fn floats(batch: &RecordBatch, name: &str) -> Vec<f64> {
+        get_floatarray_column(batch, batch.schema().index_of(name).unwrap())
+            .values()
+            .to_vec()
+    }
+
+    #[test]
+    fn plan_nodes_are_annotated_with_their_samples() {
+        let batch = load_profile();
+        let counts = count_per_operator_event(&batch);
+        let count = |operator: &str, event: &str| *counts.get(&(operator, event)).unwrap_or(&0.);
+        let event_total = |event: &str| {
+            counts
+                .iter()
+                .filter(|((_, e), _)| *e == event)
+                .map(|(_, count)| count)
+                .sum::<f64>()
+        };
+
+        let result = plan(&batch);
+        let operators = ["groupby3", "hashjoin2", "tablescan1", "tablescan4"];
+        let operator = get_stringarray_column(&result, 3);
+        assert_eq!(result.num_rows(), operators.len());
+        for (i, name) in operators.iter().enumerate() {
+            assert_eq!(operator.value(i), *name);
+        }
+        assert_eq!(floats(&result, "est_cardinality"), vec![10., -1., -1., -1.]);
+
+        let exclusive = operators
+            .iter()
+            .map(|operator| count(operator, "cycles") + count(operator, "loads"))
+            .collect::<Vec<f64>>();
+        assert_eq!(floats(&result, "count"), exclusive);
+        assert_eq!(exclusive[3], 0.);
+        assert_eq!(
+            floats(&result, "inclusive"),
+            vec![
+                exclusive.iter().sum::<f64>(),
+                exclusive[1] + exclusive[2] + exclusive[3],
+                exclusive[2],
+                exclusive[3],
+            ]
+        );
+
+        for event in ["cycles", "loads"].iter() {
+            let perc = operators
+                .iter()
+                .map(|operator| round(count(operator, event) / event_total(event)))
+                .collect::<Vec<f64>>();
+            assert_eq!(floats(&result, &format!("perc_{}", event)), perc, "{}", event);
+        }
+        assert_eq!(result.num_columns(), 13);
+    }
+}
@@ -0,0 +1,317 @@
+use std::sync::Arc;
+
+use arrow::{
+    array::{Float64Array, Int32Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    exec::{
+        basic::summary_cube::answer_from_cube,
+        rest::rest_api::{
+            eval_filters, eval_operations, eval_selections, filter_already_applied,
+            multiple_queries_concat, shared_filter_prefix, split_query, SELF_SENDING_OPERATIONS,
+        },
+    },
+    state::state::{get_query_from_cache, insert_query_to_cache},
+    utils::{
+        record_batch_util::create_new_record_batch,
+        string_util::{split_at_double_and, split_at_question_mark},
+    },
+};
+
+// Queries starting with this prefix return their explain batch instead of the result
+pub static EXPLAIN_PREFIX: &str = "explain:";
+
+struct Stage {
+    // Sub-query of a "&&" query, 0 otherwise
+    query: i32,
+    stage: &'static str,
+    name: String,
+    params: String,
+    cache: &'static str,
+    rows_in: i32,
+    rows_out: i32,
+    ms: f64,
+}
+
+#[derive(Default)]
+struct Explain {
+    stages: Vec<Stage>,
+    // An operation was skipped, the result is incomplete and not cached
+    skipped: bool,
+}
+
+impl Explain {
+    // Runs the stage and records rows and elapsed time
+    fn run<F>(
+        &mut self,
+        query: usize,
+        stage: &'static str,
+        name: String,
+        params: String,
+        batch: RecordBatch,
+        f: F,
+    ) -> RecordBatch
+    where
+        F: FnOnce(RecordBatch) -> (RecordBatch, &'static str),
+    {
+        let rows_in = batch.num_rows() as i32;
+        let timer = instant::Instant::now();
+        let (batch, cache) = f(batch);
+        self.stages.push(Stage {
+            query: query as i32,
+            stage: stage,
+            name: name,
+            params: params,
+            cache: cache,
+            rows_in: rows_in,
+            rows_out: batch.num_rows() as i32,
+            ms: timer.elapsed().as_secs_f64() * 1000.,
+        });
+        batch
+    }
+
+    // Operations one by one, then the selections
+    fn run_query(&mut self, query: usize, mut batch: RecordBatch, restful_string: &str) -> RecordBatch {
+        let (_, op_vec, _) = split_query(restful_string);
+        for op in op_vec {
+            let split = split_at_question_mark(op);
+            // Sending the chart result is not part of explaining the query
+            let stage = if SELF_SENDING_OPERATIONS.contains(&split[0]) {
+                self.skipped = true;
+                "skipped_operation"
+            } else {
+                "operation"
+            };
+            batch = self.run(
+                query,
+                stage,
+                split[0].to_string(),
+                split.get(1).unwrap_or(&"").to_string(),
+                batch,
+                |batch| {
+                    if stage == "skipped_operation" {
+                        return (batch, "-");
+                    }
+                    match eval_operations(batch.clone(), vec![op]) {
+                        Some(result) => (result, "-"),
+                        None => (batch, "-"),
+                    }
+                },
+            );
+        }
+        self.run_selections(query, batch, restful_string)
+    }
+
+    fn run_selections(&mut self, query: usize, batch: RecordBatch, restful_string: &str) -> RecordBatch {
+        let (_, _, select_vec) = split_query(restful_string);
+        if select_vec.is_empty() {
+            return batch;
+        }
+        self.run(
+            query,
+            "selection",
+            select_vec.join(","),
+            String::new(),
+            batch,
+            |batch| (eval_selections(batch, select_vec), "-"),
+        )
+    }
+
+    fn cache_result(&self, restful_string: &str, result: RecordBatch) {
+        if !self.skipped {
+            insert_query_to_cache(restful_string, result);
+        }
+    }
+
+    fn to_record_batch(&self) -> RecordBatch {
+        let stages = &self.stages;
+        create_new_record_batch(
+            vec![
+                "step", "query", "stage", "name", "params", "cache", "rows_in", "rows_out", "ms",
+            ],
+            vec![
+                DataType::Int32,
+                DataType::Int32,
+                DataType::Utf8,
+                DataType::Utf8,
+                DataType::Utf8,
+                DataType::Utf8,
+                DataType::Int32,
+                DataType::Int32,
+                DataType::Float64,
+            ],
+            vec![
+                Arc::new(Int32Array::from((0..stages.len() as i32).collect::<Vec<i32>>())),
+                Arc::new(Int32Array::from(stages.iter().map(|s| s.query).collect::<Vec<i32>>())),
+                Arc::new(StringArray::from(stages.iter().map(|s| s.stage).collect::<Vec<&str>>())),
+                Arc::new(StringArray::from(
+                    stages.iter().map(|s| s.name.as_str()).collect::<Vec<&str>>(),
+                )),
+                Arc::new(StringArray::from(
+                    stages.iter().map(|s| s.params.as_str()).collect::<Vec<&str>>(),
+                )),
+                Arc::new(StringArray::from(
+                    stages.iter().map(|s| s.cache).collect::<Vec<&str>>(),
+                )),
+                Arc::new(Int32Array::from(stages.iter().map(|s| s.rows_in).collect::<Vec<i32>>())),
+                Arc::new(Int32Array::from(stages.iter().map(|s| s.rows_out).collect::<Vec<i32>>())),
+                Arc::new(Float64Array::from(stages.iter().map(|s| s.ms).collect::<Vec<f64>>())),
+            ],
+        )
+    }
+}
+
+// Column names and values of the filters, e.g. ?operator="tablescan1" => operator, tablescan1
+fn filter_plan(filter_vec: &[&str]) -> (String, String) {
+    let mut names = Vec::new();
+    let mut params = Vec::new();
+    for filter in filter_vec {
+        let (name, value) = filter.split_once('=').unwrap_or((filter, ""));
+        names.push(name.replace("?", ""));
+        params.push(value.replace("\"", ""));
+    }
+    (names.join(","), params.join(";"))
+}
+
+// Executes the query like eval_query, stage by stage.
+// One row per stage: result cache lookup, filters, every operation and the selections,
+// operations sending their chart result themselves (heatmap) are listed as skipped_operation,
+// summary_cube instead of filters and operations if the query is rolled up from the cube,
+// for "&&" queries the shared filters and then every sub-query
+// with the cache status (hit, partial, miss, - for uncached stages), rows in and out and elapsed time.
+// The result is cached as usual unless an operation was skipped, the explain batch itself is not
+pub fn explain_query(record_batch: RecordBatch, restful_string: &str) -> RecordBatch {
+    let mut explain = Explain::default();
+
+    let timer = instant::Instant::now();
+    let cached = get_query_from_cache()
+        .lock()
+        .unwrap()
+        .get(restful_string)
+        .cloned();
+    let rows = record_batch.num_rows() as i32;
+    let cache = if cached.is_some() { "hit" } else { "miss" };
+    explain.stages.push(Stage {
+        query: 0,
+        stage: "result_cache",
+        name: restful_string.to_string(),
+        params: String::new(),
+        cache: cache,
+        rows_in: rows,
+        rows_out: cached.map(|batch| batch.num_rows() as i32).unwrap_or(rows),
+        ms: timer.elapsed().as_secs_f64() * 1000.,
+    });
+    if cache == "hit" {
+        return explain.to_record_batch();
+    }
+
+    if multiple_queries_concat(restful_string) {
+        let queries = split_at_double_and(restful_string);
+        let filter_vecs = queries
+            .iter()
+            .map(|query| split_query(query).0)
+            .collect::<Vec<Vec<&str>>>();
+        let prefix = shared_filter_prefix(&filter_vecs);
+        let (names, params) = filter_plan(&prefix);
+        let shared = explain.run(0, "shared_filter", names, params, record_batch, |batch| {
+            let (batch, cache) = filter_already_applied(batch, prefix.to_owned());
+            (batch, cache.as_str())
+        });
+
+        for (i, (query, filter_vec)) in queries.iter().zip(&filter_vecs).enumerate() {
+            let cached = get_query_from_cache().lock().unwrap().get(*query).cloned();
+            explain.stages.push(Stage {
+                query: i as i32,
+                stage: "result_cache",
+                name: query.to_string(),
+                params: String::new(),
+                cache: if cached.is_some() { "hit" } else { "miss" },
+                rows_in: shared.num_rows() as i32,
+                rows_out: cached.as_ref().map(|batch| batch.num_rows()).unwrap_or(shared.num_rows()) as i32,
+                ms: 0.,
+            });
+            if cached.is_some() {
+                continue;
+            }
+            let rest = &filter_vec[prefix.len()..];
+            let (names, params) = filter_plan(rest);
+            let filtered = explain.run(i, "filter", names, params, shared.to_owned(), |batch| {
+                (eval_filters(batch, rest.to_vec()), "-")
+            });
+            explain.skipped = false;
+            let result = explain.run_query(i, filtered, query);
+            explain.cache_result(query, result);
+        }
+        return explain.to_record_batch();
+    }
+
+    let timer = instant::Instant::now();
+    if let Some(result) = answer_from_cube(restful_string) {
+        let (_, op_vec, _) = split_query(restful_string);
+        explain.stages.push(Stage {
+            query: 0,
+            stage: "summary_cube",
+            name: op_vec.join(","),
+            params: String::new(),
+            cache: "hit",
+            rows_in: rows,
+            rows_out: result.num_rows() as i32,
+            ms: timer.elapsed().as_secs_f64() * 1000.,
+        });
+        let result = explain.run_selections(0, result, restful_string);
+        explain.cache_result(restful_string, result);
+        return explain.to_record_batch();
+    }
+
+    let (filter_vec, _, _) = split_query(restful_string);
+    let (filter_names, filter_params) = filter_plan(&filter_vec);
+    let filtered = explain.run(0, "filter", filter_names, filter_params, record_batch, |batch| {
+        let (batch, cache) = filter_already_applied(batch, filter_vec);
+        (batch, cache.as_str())
+    });
+    let result = explain.run_query(0, filtered, restful_string);
+
+    explain.cache_result(restful_string, result);
+    explain.to_record_batch()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        state::state::set_unfiltered_record_batch,
+        utils::{array_util::get_stringarray_column, test_util::profile_batch},
+    };
+
+    fn stages(explain: &RecordBatch) -> Vec<&str> {
+        let column = get_stringarray_column(explain, 2);
+        (0..explain.num_rows()).map(|i| column.value(i)).collect()
+    }
+
+    #[test]
+    fn stages_of_query() {
+        let batch = profile_batch(500);
+        set_unfiltered_record_batch(batch.clone());
+        let query = "?ev_name=\"cycles\"/count?operator/operator/count";
+        let explain = explain_query(batch.clone(), query);
+        assert_eq!(stages(&explain), vec!["result_cache", "filter", "operation", "selection"]);
+        assert!(get_query_from_cache().lock().unwrap().contains_key(query));
+
+        let explain = explain_query(batch, query);
+        assert_eq!(stages(&explain), vec!["result_cache"]);
+        assert_eq!(get_stringarray_column(&explain, 5).value(0), "hit");
+    }
+
+    #[test]
+    fn self_sending_operations_are_skipped() {
+        let batch = profile_batch(500);
+        set_unfiltered_record_batch(batch.clone());
+        let query = "?ev_name=\"cycles\"/heatmap?operator,time:0.5!-1from_to-1,0";
+        let explain = explain_query(batch, query);
+        assert_eq!(stages(&explain), vec!["result_cache", "filter", "skipped_operation"]);
+        assert!(!get_query_from_cache().lock().unwrap().contains_key(query));
+    }
+}
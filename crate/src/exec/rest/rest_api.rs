@@ -1,15 +1,22 @@
-use super::rest_api_pars::{abs_freq_pars, freq_mem, rel_freq_pars, sort};
+use super::explain;
+use super::rest_api_pars::{abs_freq_pars, count_pars, derived_pars, freq_mem, phases_pars, rel_freq_pars, sort};
 use crate::{
-    exec::basic::{
-        basic, count, filter, kpis,
-        uir::{get_top_srclines, uir},
+    exec::{
+        basic::{
+            basic, count, dso, filter, hierarchy, kpis, lineage, op_mapping, queries, sample,
+            summary_cube, timing,
+            uir::{get_top_srclines, uir},
+            srcline, uir_cost,
+        },
+        plan::{critical_path, misestimation, plan},
     },
     record_batch_util::send_record_batch_to_js,
-    state::state::{get_query_from_cache, insert_query_to_cache, get_filter_query_from_cache},
+    state::state::{get_query_from_cache, insert_query_to_cache, get_filter_query_from_cache, get_sample_record_batch},
+    web_file::validation,
     utils::{
         print_to_cons::print_to_js_with_obj,
-        record_batch_util::combine_to_one_record_batch,
-        string_util::{split_at_comma, split_at_double_and, split_at_question_mark, split_at_to}, record_batch_schema::RecordBatchSchema,
+        record_batch_util::{combine_to_one_record_batch, error_record_batch, sub_query_container},
+        string_util::{split_at_comma, split_at_double_and, split_at_question_mark, split_at_slash, split_at_to}, record_batch_schema::RecordBatchSchema,
     },
 };
 use arrow::record_batch::RecordBatch;
@@ -30,7 +37,7 @@ pub fn find_name(name: &str, batch: &RecordBatch) -> usize {
 
 // FILTER:
 // /?operator="No operator" -- for String
-fn eval_filters(record_batch: RecordBatch, mut filter_vec: Vec<&str>) -> RecordBatch {
+pub fn eval_filters(record_batch: RecordBatch, mut filter_vec: Vec<&str>) -> RecordBatch {
     if filter_vec.len() == 0 {
         return record_batch;
     } else {
@@ -69,11 +76,15 @@ fn eval_filters(record_batch: RecordBatch, mut filter_vec: Vec<&str>) -> RecordB
     }
 }
 
-fn eval_operations(mut record_batch: RecordBatch, op_vec: Vec<&str>) -> Option<RecordBatch> {
+// Operations sending their results to JS themselves, eval_operations returns None for them
+pub static SELF_SENDING_OPERATIONS: [&str; 1] = ["heatmap"];
+
+pub fn eval_operations(mut record_batch: RecordBatch, op_vec: Vec<&str>) -> Option<RecordBatch> {
     for op in op_vec {
         let split = split_at_question_mark(op);
         let operator = split[0];
-        let params = split[1];
+        // Operations without params, e.g. misestimation?
+        let params = split.get(1).copied().unwrap_or("");
 
         match operator {
             "sunburst" => {
@@ -105,9 +116,9 @@ fn eval_operations(mut record_batch: RecordBatch, op_vec: Vec<&str>) -> Option<R
             "basic_count" => {
                 record_batch = count::count(&record_batch, find_name(params, &record_batch));
             }
+            // count?<col>[;(<name>=<expr>;...)]
             "count" => {
-                record_batch =
-                    count::group_by(&record_batch, find_name(params, &record_batch))
+                record_batch = count_pars(record_batch, params);
             }
             "count_with_mapping" => {
                 record_batch = count::group_by_with_nice_op(
@@ -115,12 +126,50 @@ fn eval_operations(mut record_batch: RecordBatch, op_vec: Vec<&str>) -> Option<R
                     find_name(params, &record_batch),
                 )
             }
+            "timing" => {
+                record_batch = timing::timing_statistics(
+                    &record_batch,
+                    find_name(params, &record_batch),
+                    find_name("time", &record_batch),
+                );
+            }
+            "plan" => {
+                record_batch = plan::plan(&record_batch);
+            }
+            // misestimation[?<factor>]
+            "misestimation" => {
+                record_batch = match misestimation::parse_factor(params) {
+                    Ok(factor) => misestimation::misestimation(&record_batch, factor),
+                    Err(err) => error_record_batch(&err),
+                };
+            }
+            "queries" => {
+                record_batch = queries::query_list();
+            }
+            "query_summary" => {
+                record_batch = queries::query_summary();
+            }
+            "validation" => {
+                record_batch = validation::validation_report();
+            }
+            "gantt" => {
+                record_batch = critical_path::gantt(&record_batch);
+            }
+            "critical_path" => {
+                record_batch = critical_path::critical_path_list(&record_batch);
+            }
             "absfreq" => {
                 record_batch = abs_freq_pars(record_batch, params);
             }
             "relfreq" => {
                 record_batch = rel_freq_pars(record_batch, params);
             }
+            "derived" => {
+                record_batch = derived_pars(record_batch, params);
+            }
+            "phases" => {
+                record_batch = phases_pars(record_batch, params);
+            }
             "sort" => {
                 record_batch = sort(&record_batch, params);
             }
@@ -131,6 +180,43 @@ fn eval_operations(mut record_batch: RecordBatch, op_vec: Vec<&str>) -> Option<R
             "uir" => {
                 record_batch = uir(record_batch);
             }
+            "uir_instructions" => {
+                record_batch = uir_cost::uir_instructions(&record_batch);
+            }
+            "uir_blocks" => {
+                record_batch = uir_cost::uir_blocks(&record_batch);
+            }
+            "uir_functions" => {
+                record_batch = uir_cost::uir_functions(&record_batch);
+            }
+            "uir_cfg" => {
+                record_batch = uir_cost::uir_cfg(&record_batch);
+            }
+            "dso" => {
+                record_batch = dso::dso_breakdown(&record_batch, params == "pipeline");
+            }
+            "srcline" => {
+                record_batch = srcline::srcline(&record_batch, params == "function");
+            }
+            "operator_info" => {
+                record_batch = op_mapping::operator_info(&record_batch);
+            }
+            // lineage?<pipeline|operator|op_ext|physical_op>[,<parent>]
+            "lineage" => {
+                let params = split_at_comma(params);
+                let level = lineage::LineageLevel::from_name(params.get(0).copied().unwrap_or(""));
+                record_batch = match level {
+                    Ok(level) => lineage::lineage(&record_batch, level, params.get(1).copied()),
+                    Err(err) => error_record_batch(&err),
+                };
+            }
+            // hierarchy?<col1>,...,<colN>[;<metric>]
+            "hierarchy" => {
+                record_batch = hierarchy::hierarchy(&record_batch, params);
+            }
+            "physical_breakdown" => {
+                record_batch = lineage::physical_breakdown(&record_batch);
+            }
             "top(srclines)" => {
                 let order = match params {
                     "cycles::ppp" => 0,
@@ -150,7 +236,7 @@ fn eval_operations(mut record_batch: RecordBatch, op_vec: Vec<&str>) -> Option<R
     return Some(record_batch);
 }
 
-fn eval_selections(record_batch: RecordBatch, select_vec: Vec<&str>) -> RecordBatch {
+pub fn eval_selections(record_batch: RecordBatch, select_vec: Vec<&str>) -> RecordBatch {
     let mut selections = Vec::new();
 
     for select in select_vec {
@@ -170,47 +256,106 @@ fn query_already_calculated(restful_string: &str) -> bool {
     return false;
 }
 
-fn filter_already_applied(batch: RecordBatch, filter_vec: Vec<&str>) -> RecordBatch {
-    let str_raw = filter_vec.join("");
+// How the filters of a query were served
+#[derive(Clone, Copy, PartialEq)]
+pub enum FilterCache {
+    Hit,
+    // Remaining filters applied to the cached result of a subset of the filters
+    Partial,
+    Miss,
+}
+
+impl FilterCache {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterCache::Hit => "hit",
+            FilterCache::Partial => "partial",
+            FilterCache::Miss => "miss",
+        }
+    }
+}
+
+// Default selects the first event of the rows left by the preceding filters, so filters with
+// a Default value depend on their order
+fn depends_on_order(filters: &[&str]) -> bool {
+    filters.iter().any(|filter| {
+        filter.split_terminator("=").nth(1).map(|value| value.replace("\"", ""))
+            == Some("Default".to_string())
+    })
+}
+
+// First line of the keys of filters which depend on their order
+static ORDERED_KEY: &str = "ordered";
+
+// Filters form a conjunction, the cache key is the sorted set of filters, one per line.
+// Filters depending on their order are kept in order behind ORDERED_KEY
+fn filter_cache_key(filters: &[&str], ordered: bool) -> String {
+    if ordered {
+        return [&[ORDERED_KEY][..], filters].concat().join("\n");
+    }
+    filters.join("\n")
+}
+
+// Filters of a conjunction, None for filters depending on their order
+fn filters_of_key(key: &str) -> Option<Vec<&str>> {
+    let filters = key.split_terminator('\n').collect::<Vec<&str>>();
+    if filters.first() == Some(&ORDERED_KEY) {
+        return None;
+    }
+    Some(filters)
+}
+
+// Serves the filters from the filter cache: an exact hit, or the cached result of a subset of
+// the filters with the fewest rows, on which only the remaining filters are evaluated.
+// Filters depending on their order are only served by exact hits.
+// batch is the unfiltered batch, the result is cached
+pub fn filter_already_applied(batch: RecordBatch, filter_vec: Vec<&str>) -> (RecordBatch, FilterCache) {
+    let ordered = depends_on_order(&filter_vec);
+    let mut filters = filter_vec.to_owned();
+    if !ordered {
+        filters.sort();
+        filters.dedup();
+    }
+    let key = filter_cache_key(&filters, ordered);
+
     let cache = get_filter_query_from_cache();
     let mut query = cache.lock().unwrap();
-    if let Some(batch) = query.get(&str_raw) {
-        return batch.to_owned();
+    if let Some(batch) = query.get(&key) {
+        return (batch.to_owned(), FilterCache::Hit);
     }
 
-    let mut vec_without_time = Vec::new();
-    let mut vec_time = Vec::new();
-    let mut has_time = false;
-    for entry in filter_vec.to_owned() {
-        if entry.starts_with("?time") {
-            vec_time.push(entry);
-            has_time = true;
-        } else {
-            vec_without_time.push(entry);
+    let mut base: Option<(Vec<&str>, &RecordBatch)> = None;
+    for (cached_key, cached_batch) in query.iter().filter(|_| !ordered) {
+        let cached_filters = match filters_of_key(cached_key) {
+            Some(cached_filters) => cached_filters,
+            None => continue,
+        };
+        if cached_filters.is_empty() || !cached_filters.iter().all(|filter| filters.contains(filter)) {
+            continue;
+        }
+        if base.as_ref().map_or(true, |(_, base)| cached_batch.num_rows() < base.num_rows()) {
+            base = Some((cached_filters, cached_batch));
         }
     }
 
-    if has_time {
-        let str_raw_without_time = vec_without_time.join("");
-        if let Some(batch) = query.get(&str_raw_without_time) {
-            let time_filtered_batch = eval_filters(batch.to_owned(), vec_time);
-            return time_filtered_batch;
-        } else {
-            let filtered_batch = eval_filters(batch.to_owned(), vec_without_time);
-            query.insert(str_raw_without_time, filtered_batch.to_owned());
-            eval_filters(batch.to_owned(), vec_time);
-            return filtered_batch;
+    let (filtered_batch, status) = match base {
+        Some((applied, base)) => {
+            let remaining = filters
+                .iter()
+                .filter(|filter| !applied.contains(filter))
+                .copied()
+                .collect::<Vec<&str>>();
+            (eval_filters(base.to_owned(), remaining), FilterCache::Partial)
         }
-    }
-    
+        None => (eval_filters(batch.to_owned(), filters.to_owned()), FilterCache::Miss),
+    };
 
-    let filtered_batch = eval_filters(batch, filter_vec);
-    query.insert(str_raw, filtered_batch.to_owned());
-    return filtered_batch;
+    query.insert(key, filtered_batch.to_owned());
+    return (filtered_batch, status);
 }
 
-fn split_query(restful_string: &str) -> (Vec<&str>, Vec<&str>, Vec<&str>) {
-    let split = restful_string.split_terminator("/");
+pub fn split_query(restful_string: &str) -> (Vec<&str>, Vec<&str>, Vec<&str>) {
+    let split = split_at_slash(restful_string);
 
     let mut filter_vec = Vec::new();
     let mut op_vec = Vec::new();
@@ -229,14 +374,20 @@ fn split_query(restful_string: &str) -> (Vec<&str>, Vec<&str>, Vec<&str>) {
     return (filter_vec, op_vec, select_vec);
 }
 
-fn multiple_queries_concat(restful_string: &str) -> bool {
+pub fn multiple_queries_concat(restful_string: &str) -> bool {
     restful_string.contains("&&")
 }
 
 fn exec_query(record_batch: RecordBatch, restful_string: &str) -> Option<RecordBatch> {
     let split_query = split_query(restful_string);
-    let record_batch = filter_already_applied(record_batch, split_query.0);
-    let record_batch = eval_operations(record_batch, split_query.1);
+    // Counts and frequencies are rolled up from the summary cube without filtering the samples
+    let record_batch = match summary_cube::answer_from_cube(restful_string) {
+        Some(batch) => Some(batch),
+        None => {
+            let (record_batch, _) = filter_already_applied(record_batch, split_query.0);
+            eval_operations(record_batch, split_query.1)
+        }
+    };
     if let Some(batch) = record_batch {
         let record_batch = eval_selections(batch, split_query.2);
         return Some(record_batch);
@@ -256,9 +407,29 @@ fn exec_query_without_filters(record_batch: RecordBatch, restful_string: &str) -
     }
 }
 
-fn exec_filters(record_batch: RecordBatch, restful_string: &str) -> RecordBatch {
-    let split_query = split_query(restful_string);
-    return eval_filters(record_batch, split_query.0);
+// Filters all sub-queries of a "&&" query start with, they are evaluated once
+pub fn shared_filter_prefix<'a>(filter_vecs: &[Vec<&'a str>]) -> Vec<&'a str> {
+    let mut prefix = filter_vecs.first().cloned().unwrap_or(Vec::new());
+    for filter_vec in filter_vecs {
+        let common = prefix
+            .iter()
+            .zip(filter_vec)
+            .take_while(|(a, b)| a == b)
+            .count();
+        prefix.truncate(common);
+    }
+    prefix
+}
+
+// Results of equal length are combined column by column into one batch (e.g. KPIs),
+// otherwise they are sent as one container batch with a row per sub-query
+pub fn send_sub_query_results(vec_batch: Vec<RecordBatch>, queries: Vec<&str>, restful_string: &str) {
+    let rows = vec_batch.first().map(|batch| batch.num_rows());
+    if vec_batch.iter().all(|batch| Some(batch.num_rows()) == rows) {
+        finish_query_exec(combine_to_one_record_batch(vec_batch), restful_string);
+    } else {
+        finish_query_exec(sub_query_container(&vec_batch, &queries), restful_string);
+    }
 }
 
 pub fn finish_query_exec(record_batch: RecordBatch, restful_string: &str) {
@@ -270,22 +441,58 @@ pub fn finish_query_exec(record_batch: RecordBatch, restful_string: &str) {
     insert_query_to_cache(restful_string, record_batch);
 }
 
+// Queries starting with this prefix are answered on the sample first and exactly afterwards
+pub static APPROXIMATE_PREFIX: &str = "approx:";
+
+// Result on the sample with scaled counts and confidence intervals.
+// Skipped if the exact result is cached, the profile is small enough to have no sample,
+// for "&&" queries and for operations not counting samples (see sample::approximable)
+fn send_approximate_result(restful_string: &str) {
+    let sample = match get_sample_record_batch() {
+        Some(sample) => sample,
+        None => return,
+    };
+    if get_query_from_cache().lock().unwrap().contains_key(restful_string)
+        || multiple_queries_concat(restful_string)
+    {
+        return;
+    }
+    let (filter_vec, op_vec, _) = split_query(restful_string);
+    if !sample::approximable(&op_vec) {
+        return;
+    }
+
+    let filtered = eval_filters(sample.batch.to_owned(), filter_vec);
+    if let Some(batch) = exec_query_without_filters(filtered, restful_string) {
+        send_record_batch_to_js(&sample::approximate_result(&batch, &sample));
+    }
+}
+
 pub fn eval_query(record_batch: RecordBatch, restful_string: &str) {
 
     print_to_js_with_obj(&format!("{:?}", restful_string).into());
 
+    if let Some(restful_string) = restful_string.strip_prefix(explain::EXPLAIN_PREFIX) {
+        send_record_batch_to_js(&explain::explain_query(record_batch, restful_string));
+        return;
+    }
+
+    if let Some(restful_string) = restful_string.strip_prefix(APPROXIMATE_PREFIX) {
+        send_approximate_result(restful_string);
+        eval_query(record_batch, restful_string);
+        return;
+    }
+
     if query_already_calculated(restful_string) {
         return;
     }
 
     if multiple_queries_concat(restful_string) {
-        let split = split_at_double_and(restful_string);
-        let mut vec_batch = Vec::new();
-        let filtered = exec_filters(record_batch, restful_string); 
-        for query in split {
-            vec_batch.push(exec_query_without_filters(filtered.to_owned(), query).unwrap());
+        let queries = split_at_double_and(restful_string);
+        match sub_query_results(record_batch, &queries) {
+            Ok(vec_batch) => send_sub_query_results(vec_batch, queries, restful_string),
+            Err(err) => send_record_batch_to_js(&error_record_batch(&err)),
         }
-        finish_query_exec(combine_to_one_record_batch(vec_batch), restful_string);
     } else {
         let batch = exec_query(record_batch, restful_string);
         if let Some(batch) = batch {
@@ -293,3 +500,133 @@ pub fn eval_query(record_batch: RecordBatch, restful_string: &str) {
         }
     }
 }
+
+// Results of the sub-queries of a "&&" query, the shared leading filters are applied once.
+// Sub-queries with operations sending their results themselves are rejected
+fn sub_query_results(record_batch: RecordBatch, queries: &[&str]) -> Result<Vec<RecordBatch>, String> {
+    for query in queries {
+        let (_, op_vec, _) = split_query(query);
+        if let Some(op) = op_vec
+            .iter()
+            .find(|op| SELF_SENDING_OPERATIONS.contains(&split_at_question_mark(op)[0]))
+        {
+            return Err(format!("{} is not supported in combined queries", op));
+        }
+    }
+
+    let filter_vecs = queries
+        .iter()
+        .map(|query| split_query(query).0)
+        .collect::<Vec<Vec<&str>>>();
+    let prefix = shared_filter_prefix(&filter_vecs);
+    let (shared, _) = filter_already_applied(record_batch, prefix.to_owned());
+
+    let mut vec_batch = Vec::new();
+    for (query, filter_vec) in queries.iter().zip(&filter_vecs) {
+        let cached = get_query_from_cache().lock().unwrap().get(*query).cloned();
+        let batch = match cached {
+            Some(batch) => batch,
+            None => {
+                let filtered = eval_filters(shared.to_owned(), filter_vec[prefix.len()..].to_vec());
+                let batch = exec_query_without_filters(filtered, query).unwrap();
+                insert_query_to_cache(query, batch.to_owned());
+                batch
+            }
+        };
+        vec_batch.push(batch);
+    }
+    Ok(vec_batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{state::state::set_unfiltered_record_batch, utils::test_util::profile_batch};
+
+    fn assert_same_rows(cached: &RecordBatch, uncached: &RecordBatch) {
+        assert_eq!(cached.num_rows(), uncached.num_rows());
+        for (a, b) in cached.columns().iter().zip(uncached.columns()) {
+            assert_eq!(a.as_ref(), b.as_ref());
+        }
+    }
+
+    #[test]
+    fn invalid_misestimation_factor_is_reported() {
+        let result = eval_operations(profile_batch(10), vec!["misestimation?x"]).unwrap();
+        assert_eq!(result.schema().field(0).name(), "error");
+    }
+
+    #[test]
+    fn filter_cache_equals_uncached_evaluation() {
+        let batch = profile_batch(1000);
+        let operator = "?operator=\"tablescan1,hashjoin2\"";
+        let event = "?ev_name=\"cycles\"";
+        let time = "?time=\"0.5from_to2.5\"";
+        let default = "?ev_name=\"Default\"";
+        let loads = "?ev_name=\"loads\"";
+
+        for (filters, expected) in [
+            (vec![operator], FilterCache::Miss),
+            (vec![operator], FilterCache::Hit),
+            (vec![event, operator], FilterCache::Partial),
+            (vec![operator, event], FilterCache::Hit),
+            (vec![time, event, operator], FilterCache::Partial),
+            (vec![time], FilterCache::Miss),
+            (vec![time, event], FilterCache::Partial),
+            // Default is the first event of the rows left by the preceding filters
+            (vec![operator, default], FilterCache::Miss),
+            (vec![operator, default], FilterCache::Hit),
+            (vec![default, operator], FilterCache::Miss),
+            (vec![time, operator, default], FilterCache::Miss),
+            (vec![loads], FilterCache::Miss),
+            (vec![loads, default], FilterCache::Miss),
+            (vec![loads, default, operator], FilterCache::Miss),
+        ] {
+            let (cached, status) = filter_already_applied(batch.clone(), filters.clone());
+            let uncached = eval_filters(batch.clone(), filters.clone());
+            assert!(status == expected, "{:?}: {}", filters, status.as_str());
+            assert!(uncached.num_rows() > 0);
+            assert_same_rows(&cached, &uncached);
+        }
+    }
+
+    #[test]
+    fn partial_hits_use_the_smallest_subset() {
+        let batch = profile_batch(1000);
+        let operator = "?operator=\"tablescan1\"";
+        let event = "?ev_name=\"cycles\"";
+        let pipeline = "?pipeline=\"pipeline1\"";
+        filter_already_applied(batch.clone(), vec![event]);
+        filter_already_applied(batch.clone(), vec![operator, pipeline]);
+
+        let filters = vec![pipeline, event, operator];
+        let (cached, status) = filter_already_applied(batch.clone(), filters.clone());
+        assert!(status == FilterCache::Partial);
+        assert_same_rows(&cached, &eval_filters(batch, filters));
+    }
+
+    #[test]
+    fn sub_queries_share_leading_filters() {
+        let a = "?operator=\"a\"";
+        let b = "?ev_name=\"b\"";
+        let c = "?pipeline=\"c\"";
+        assert_eq!(shared_filter_prefix(&[vec![a, b, c], vec![a, b], vec![a, b, c]]), vec![a, b]);
+        assert_eq!(shared_filter_prefix(&[vec![a, b], vec![b, a]]), Vec::<&str>::new());
+        assert_eq!(shared_filter_prefix(&[]), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn self_sending_sub_queries_are_rejected() {
+        let batch = profile_batch(100);
+        set_unfiltered_record_batch(batch.clone());
+        let queries = [
+            "operator/count/?ev_name=\"cycles\"/count?operator",
+            "?ev_name=\"cycles\"/heatmap?operator,time:0.5!-1from_to-1,0",
+        ];
+        assert!(sub_query_results(batch.clone(), &queries).is_err());
+        assert!(get_query_from_cache().lock().unwrap().is_empty());
+
+        let results = sub_query_results(batch, &queries[..1]).unwrap();
+        assert_eq!(results[0].num_rows(), 4);
+    }
+}
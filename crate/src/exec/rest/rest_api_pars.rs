@@ -2,13 +2,14 @@ use arrow::record_batch::RecordBatch;
 
 use crate::{
     exec::{
-        basic::basic,
+        basic::{basic, count, derived},
         freq::{
             abs_freq,
-            freq::{freq_of_memory, MEM},
-            rel_freq,
+            freq::{freq_of_memory, operator_bucket, MEM},
+            phases, rel_freq,
         },
     },
+    utils::record_batch_util::error_record_batch,
     utils::string_util::{
         split_at_and, split_at_colon, split_at_comma, split_at_excl_mark, split_at_numop,
         split_at_to,
@@ -17,14 +18,112 @@ use crate::{
 
 use super::rest_api::find_name;
 
+// Column the frequency is calculated for, operators by default or thread/core/binary
+pub fn freq_dimension(field: &str) -> &str {
+    match field {
+        "tid" | "cpu" | "dso" => field,
+        _ => "operator",
+    }
+}
+
+// Count per group, derived metrics can be appended to params, see derived::split_metrics
+pub fn count_pars(record_batch: RecordBatch, params: &str) -> RecordBatch {
+    let (column, metrics) = derived::split_metrics(params);
+    let result = count::group_by(&record_batch, find_name(column, &record_batch));
+    match metrics.map(derived::parse_metrics) {
+        Some(Ok(metrics)) => {
+            derived::append_metrics(&result, &record_batch, Some(column), None, &metrics)
+        }
+        Some(Err(err)) => error_record_batch(&err),
+        None => result,
+    }
+}
+
+// Bucket sizes have to be positive, the buckets would never reach the last sample otherwise
+fn parse_bucket_size(bucket_size: Option<&str>) -> Result<f64, String> {
+    match bucket_size.map(|x| x.parse::<f64>()) {
+        Some(Ok(parsed)) if parsed > 0. => Ok(parsed),
+        _ => Err(format!("Invalid bucket size {:?}", bucket_size.unwrap_or(""))),
+    }
+}
+
+// Buckets of a freq operation, like in abs_freq_of_event or freq_of_operators
+fn freq_buckets(params: &str, by_event: bool) -> Result<derived::Buckets, String> {
+    let split = split_at_excl_mark(params);
+    let bucket_size = parse_bucket_size(split_at_colon(split[0]).get(1).copied())?;
+    if by_event {
+        return Ok(derived::Buckets {
+            start: bucket_size,
+            bucket_size,
+            label: abs_freq::event_bucket,
+        });
+    }
+
+    let range = split.get(3).copied().unwrap_or("");
+    let from = match split_at_to(range).get(0).map(|x| x.parse::<f64>()) {
+        Some(Ok(from)) => from,
+        _ => return Err(format!("Invalid time range {:?}", range)),
+    };
+    Ok(derived::Buckets {
+        start: if from == -1. { bucket_size } else { from + bucket_size },
+        bucket_size,
+        label: operator_bucket,
+    })
+}
+
+// Result of the freq operation with the derived metrics appended, see derived::append_metrics
+fn freq_metrics(
+    record_batch: RecordBatch,
+    params: &str,
+    metrics: &str,
+    by_event: bool,
+    freq: fn(RecordBatch, &str) -> RecordBatch,
+) -> RecordBatch {
+    if params.contains("&") {
+        return error_record_batch("Derived metrics are not supported for two events");
+    }
+    let metrics = match derived::parse_metrics(metrics) {
+        Ok(metrics) => metrics,
+        Err(err) => return error_record_batch(&err),
+    };
+    let buckets = match freq_buckets(params, by_event) {
+        Ok(buckets) => buckets,
+        Err(err) => return error_record_batch(&err),
+    };
+
+    let result = freq(record_batch.clone(), params);
+    let dimension = if by_event {
+        None
+    } else {
+        let fields = split_at_colon(split_at_excl_mark(params)[0])[0];
+        Some(freq_dimension(split_at_comma(fields)[0]))
+    };
+    derived::append_metrics(&result, &record_batch, dimension, Some(buckets), &metrics)
+}
+
+// Frequencies over time, derived metrics can be appended to params, see derived::split_metrics
 pub fn abs_freq_pars(record_batch: RecordBatch, params: &str) -> RecordBatch {
+    let (params, metrics) = derived::split_metrics(params);
+    match metrics {
+        Some(metrics) => {
+            let fields = split_at_colon(params)[0];
+            let by_event = !fields.contains("pipeline")
+                && freq_dimension(split_at_comma(fields)[0]) == "operator";
+            freq_metrics(record_batch, params, metrics, by_event, abs_freq)
+        }
+        None => abs_freq(record_batch, params),
+    }
+}
+
+fn abs_freq(record_batch: RecordBatch, params: &str) -> RecordBatch {
     let split_fields_bucket_size = split_at_colon(params);
     let fields = split_fields_bucket_size[0];
+    let dimension = freq_dimension(split_at_comma(fields)[0]);
 
     if params.contains("&") {
         return abs_freq_double_event_pipeline(record_batch, params);
     } else {
-        if !fields.contains("pipeline") {
+        if !fields.contains("pipeline") && dimension == "operator" {
             let bucket_size = split_fields_bucket_size[1].parse::<f64>().unwrap();
 
             return abs_freq::abs_freq_of_event(
@@ -45,7 +144,7 @@ pub fn abs_freq_pars(record_batch: RecordBatch, params: &str) -> RecordBatch {
 
             return abs_freq::abs_freq_operators(
                 &record_batch,
-                find_name("operator", &record_batch),
+                find_name(dimension, &record_batch),
                 find_name("time", &record_batch),
                 bucket_size,
                 pipeline_vec,
@@ -110,12 +209,12 @@ pub fn rel_freq_specific_pipelines(record_batch: RecordBatch, params: &str) -> R
     let bucket_size = split_fields_bucket_size[after_colon]
         .parse::<f64>()
         .unwrap();
-    let _pipeline = field_vec[0];
+    let dimension = freq_dimension(field_vec[0]);
     let time = field_vec[1];
 
     return rel_freq::rel_freq_operators(
         &record_batch,
-        find_name("operator", &record_batch),
+        find_name(dimension, &record_batch),
         find_name(time, &record_batch),
         bucket_size,
         pipeline_vec,
@@ -126,10 +225,16 @@ pub fn rel_freq_specific_pipelines(record_batch: RecordBatch, params: &str) -> R
 }
 
 pub fn rel_freq_pars(record_batch: RecordBatch, params: &str) -> RecordBatch {
+    let (params, metrics) = derived::split_metrics(params);
     let split_fields_bucket_size = split_at_colon(params);
     let _fields = split_fields_bucket_size[0];
 
-    return rel_freq_specific_pipelines(record_batch, params);
+    match metrics {
+        Some(metrics) => {
+            freq_metrics(record_batch, params, metrics, false, rel_freq_specific_pipelines)
+        }
+        None => rel_freq_specific_pipelines(record_batch, params),
+    }
 }
 
 pub fn sort(record_batch: &RecordBatch, params: &str) -> RecordBatch {
@@ -182,3 +287,62 @@ pub fn freq_mem(record_batch: RecordBatch, params: &str) {
         abs_or_diff,
     );
 }
+
+pub fn derived_pars(record_batch: RecordBatch, params: &str) -> RecordBatch {
+    let split = split_at_excl_mark(params);
+    let split_fields_bucket_size = split_at_colon(split[0]);
+    let field_vec = split_at_comma(split_fields_bucket_size[0]);
+
+    let metrics = match derived::parse_metrics(split.get(1).unwrap_or(&"")) {
+        Ok(metrics) => metrics,
+        Err(err) => return error_record_batch(&err),
+    };
+
+    let bucket_size = match split_fields_bucket_size.get(1) {
+        Some(bucket_size) => match parse_bucket_size(Some(bucket_size)) {
+            Ok(bucket_size) => Some(bucket_size),
+            Err(err) => return error_record_batch(&err),
+        },
+        None => None,
+    };
+
+    let time = field_vec.get(1).unwrap_or(&"time");
+
+    derived::derived_metrics(
+        &record_batch,
+        find_name(field_vec[0], &record_batch),
+        find_name(time, &record_batch),
+        bucket_size,
+        metrics,
+    )
+}
+
+pub fn phases_pars(record_batch: RecordBatch, params: &str) -> RecordBatch {
+    let split = split_at_excl_mark(params);
+    let split_fields_bucket_size = split_at_colon(split[0]);
+    let bucket_size = match parse_bucket_size(split_fields_bucket_size.get(1).copied()) {
+        Ok(bucket_size) => bucket_size,
+        Err(err) => return error_record_batch(&err),
+    };
+
+    // Optional: threshold,min_buckets
+    let options = split.get(1).map(|x| split_at_comma(x)).unwrap_or(Vec::new());
+    let threshold = match options.get(0).map(|x| x.parse::<f64>()) {
+        None => 0.3,
+        Some(Ok(threshold)) if threshold >= 0. && threshold <= 1. => threshold,
+        _ => return error_record_batch(&format!("Invalid threshold {:?}", options[0])),
+    };
+    let min_buckets = match options.get(1).map(|x| x.parse::<usize>()) {
+        None => 2,
+        Some(Ok(min_buckets)) => min_buckets,
+        _ => return error_record_batch(&format!("Invalid min_buckets {:?}", options[1])),
+    };
+
+    phases::phases(
+        &record_batch,
+        find_name(split_fields_bucket_size[0], &record_batch),
+        bucket_size,
+        threshold,
+        min_buckets,
+    )
+}
@@ -7,41 +7,70 @@ extern crate console_error_panic_hook;
 
 // Arrow
 extern crate arrow;
+use arrow::datatypes::Schema;
 use arrow::record_batch::RecordBatch;
+use std::sync::Arc;
 
 // Reader
 mod web_file {
-    pub mod parquet_reader;
+    pub mod archive;
+    pub mod query_plan;
     pub mod serde_reader;
     pub mod streambuf;
+    pub mod uir_program;
+    pub mod validation;
     pub mod web_file_chunkreader;
+    pub mod zip_entry;
 }
 
 use crate::web_file::serde_reader::SerdeDict;
+use crate::web_file::archive::read_archive_queries;
+use crate::web_file::validation::{validate_archive, validation_report};
+use crate::exec::basic::sample::build_sample;
+use crate::exec::basic::summary_cube::build_summary_cube;
+use crate::exec::basic::op_mapping::{extend_operator_table, load_operator_table};
 
 // Analyze
 mod exec {
     pub mod freq {
         pub mod abs_freq;
         pub mod freq;
+        pub mod phases;
         pub mod rel_freq;
     }
     pub mod basic {
         pub mod basic;
         pub mod count;
+        pub mod derived;
+        pub mod dso;
         pub mod filter;
+        pub mod groupby;
+        pub mod hierarchy;
         pub mod kpis;
+        pub mod lineage;
         pub mod statistics;
+        pub mod timing;
         pub mod uir;
+        pub mod uir_cost;
         pub mod op_mapping;
+        pub mod queries;
+        pub mod sample;
+        pub mod srcline;
+        pub mod summary_cube;
+    }
+    pub mod plan {
+        pub mod critical_path;
+        pub mod misestimation;
+        pub mod plan;
     }
     pub mod rest {
+        pub mod explain;
         pub mod rest_api;
         pub mod rest_api_pars;
     }
 }
 
-use exec::rest::rest_api::eval_query;
+use exec::rest::rest_api::{eval_query, split_query};
 
 // Utils
 mod utils {
@@ -51,22 +80,36 @@ mod utils {
     pub mod string_util;
     pub mod record_batch_schema;
     pub mod array_util;
+    #[cfg(test)]
+    pub mod test_util;
 }
 
 use crate::utils::bindings::notify_js_finished_reading;
 use utils::bindings;
 use utils::print_to_cons::print_to_js_with_obj;
-use utils::record_batch_util;
+use utils::record_batch_util::{self, send_record_batch_to_js};
+use utils::string_util::split_at_question_mark;
 
 // State
 mod state {
+    pub mod session;
     pub mod state;
 }
 use crate::state::state::clear_cache;
+use crate::state::state::clear_entry_buffers;
 use crate::state::state::set_file_size;
 use crate::state::state::set_unfiltered_record_batch;
+use crate::state::state::{set_sample_record_batch, set_summary_cube};
 use crate::state::state::set_serde_dict;
+use crate::state::state::reset_unfiltered_record_batch;
+use crate::state::state::set_validation_report;
+use crate::state::state::get_archive_queries;
+use crate::state::state::set_archive_queries;
+use crate::state::state::get_file_size;
+use crate::state::state::set_selected_query;
+use crate::state::state::get_selected_query;
 use state::state::get_unfiltered_record_batch;
+use state::session;
 
 // TIMER
 fn start_timer() -> instant::Instant {
@@ -79,8 +122,10 @@ fn stop_timer(now: instant::Instant) {
 }
 
 // RECORD_BATCHES
-fn init_batches(file_size: i32) -> Vec<RecordBatch> {
+// None if the samples can't be read, see the validation report
+fn init_batches(file_size: i32) -> Option<Vec<RecordBatch>> {
     let serde_reader = SerdeDict::read_dict(file_size as u64);
+    bindings::send_js_query_plan(serde_reader.query_plan_json.to_owned());
     set_serde_dict(serde_reader);
     set_file_size(file_size as u64);
     record_batch_util::init_record_batches(file_size)
@@ -88,6 +133,8 @@ fn init_batches(file_size: i32) -> Vec<RecordBatch> {
 
 fn create_one_record_batch(batches: Vec<RecordBatch>) {
     let record_batch = record_batch_util::convert(batches);
+    set_sample_record_batch(build_sample(&record_batch));
+    set_summary_cube(build_summary_cube(&record_batch));
     set_unfiltered_record_batch(record_batch);
 }
 
@@ -95,14 +142,110 @@ fn create_one_record_batch(batches: Vec<RecordBatch>) {
 #[wasm_bindgen(js_name = "analyzeFile")]
 pub fn analyze_file(file_size: i32) {
     clear_cache();
+    // Buffers belong to the previous archive
+    clear_entry_buffers();
+    let report = validate_archive(file_size as u64);
+    let valid = report.is_valid();
+    set_validation_report(report);
+    set_archive_queries(read_archive_queries(file_size as u64));
+    load_operator_table(file_size as u64);
+    if !valid {
+        // Only the validation report can be requested
+        reset_unfiltered_record_batch();
+        notify_js_finished_reading(0);
+        return;
+    }
     let timer = start_timer();
     let batches = init_batches(file_size);
     stop_timer(timer);
-    create_one_record_batch(batches);
+    match batches {
+        Some(batches) => create_one_record_batch(batches),
+        None => reset_unfiltered_record_batch(),
+    }
+    notify_js_finished_reading(0);
+}
+
+// Loads another query of a multi-query archive
+#[wasm_bindgen(js_name = "selectQuery")]
+pub fn select_query(query_name: &str) {
+    let index = get_archive_queries()
+        .iter()
+        .position(|query| query.name == query_name);
+    if let (Some(index), Some(file_size)) = (index, get_file_size()) {
+        clear_cache();
+        set_selected_query(index);
+        let timer = start_timer();
+        let batches = init_batches(file_size as i32);
+        stop_timer(timer);
+        match batches {
+            Some(batches) => create_one_record_batch(batches),
+            None => reset_unfiltered_record_batch(),
+        }
+    } else if index.is_none() {
+        print_to_js_with_obj(&format!("Unknown query {:?}", query_name).into());
+    } else if index != Some(get_selected_query()) {
+        // Imported sessions only contain the samples of one query
+        print_to_js_with_obj(
+            &format!("Query {:?} can't be loaded without the archive", query_name).into(),
+        );
+    }
+    notify_js_finished_reading(0);
+}
+
+// Operator symbols supplied by the user as JSON object, kind => symbol
+#[wasm_bindgen(js_name = "setOperatorMapping")]
+pub fn set_operator_mapping(json: &str) {
+    match extend_operator_table(json) {
+        // Display names of cached results are outdated
+        Ok(()) => clear_cache(),
+        Err(err) => print_to_js_with_obj(&format!("Invalid operator mapping: {}", err).into()),
+    }
+}
+
+// Loaded profile as binary blob, with include_caches the cached query results as well
+#[wasm_bindgen(js_name = "exportSession")]
+pub fn export_session(include_caches: bool) -> Vec<u8> {
+    match session::export_session(include_caches) {
+        Ok(session) => session,
+        Err(err) => {
+            print_to_js_with_obj(&format!("Session export failed: {}", err).into());
+            Vec::new()
+        }
+    }
+}
+
+// Restores an exported session instead of analyzing the archive again
+#[wasm_bindgen(js_name = "importSession")]
+pub fn import_session(session: &[u8]) {
+    match session::import_session(session) {
+        Ok(query_plan_json) => bindings::send_js_query_plan(query_plan_json),
+        // The loaded profile is kept, nothing is changed before the session is validated
+        Err(err) => print_to_js_with_obj(&format!("Session import failed: {}", err).into()),
+    }
     notify_js_finished_reading(0);
 }
 
+// Operations answered without a loaded profile, e.g. after the validation failed
+static PROFILE_FREE_OPERATIONS: [&str; 2] = ["validation", "queries"];
+
 #[wasm_bindgen(js_name = "requestChartData")]
 pub fn request_chart_data(rest_query: &str) {
-    eval_query(get_unfiltered_record_batch().unwrap().batch.clone(), rest_query);
+    let record_batch = match get_unfiltered_record_batch() {
+        Some(shared) => shared.batch.clone(),
+        None => {
+            let (_, op_vec, _) = split_query(rest_query);
+            let profile_free = !op_vec.is_empty()
+                && op_vec
+                    .iter()
+                    .all(|op| PROFILE_FREE_OPERATIONS.contains(&split_at_question_mark(op)[0]));
+            if !profile_free {
+                // The validation report tells why no profile is loaded
+                print_to_js_with_obj(&format!("No profile loaded for {:?}", rest_query).into());
+                send_record_batch_to_js(&validation_report());
+                return;
+            }
+            RecordBatch::new_empty(Arc::new(Schema::empty()))
+        }
+    };
+    eval_query(record_batch, rest_query);
 }
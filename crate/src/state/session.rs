@@ -0,0 +1,317 @@
+use std::{collections::HashMap, convert::TryInto, io::Cursor};
+
+use arrow::{
+    ipc::{reader::StreamReader, writer::StreamWriter},
+    record_batch::RecordBatch,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    exec::basic::{
+        queries::query_summary, sample::build_sample, summary_cube::build_summary_cube,
+    },
+    state::state::{
+        clear_cache, clear_entry_buffers, get_archive_queries, get_filter_query_from_cache,
+        get_mapping_operator, get_operator_table, get_query_from_cache, get_selected_query,
+        get_serde_dict, get_unfiltered_record_batch, get_validation_report,
+        insert_mapping_hashmap, reset_file_size, set_archive_queries, set_operator_table,
+        set_query_summary, set_sample_record_batch, set_selected_query, set_serde_dict,
+        set_summary_cube, set_unfiltered_record_batch, set_validation_report,
+    },
+    web_file::{
+        archive::QueryFiles, query_plan::QueryPlan, serde_reader::SerdeDict,
+        validation::ValidationReport,
+    },
+};
+
+static SESSION_MAGIC: &[u8] = b"UMBRAPERF_SESSION";
+// Sessions of other versions are rejected, the profile has to be loaded again
+static SESSION_VERSION: u32 = 2;
+
+// Everything of the loaded profile except the batches
+#[derive(Serialize, Deserialize)]
+struct SessionHeader {
+    // Names of all queries of the archive, only the selected one has its samples in the session
+    archive_queries: Vec<String>,
+    selected_query: usize,
+    dict: SerdeDict,
+    operator_table: HashMap<String, String>,
+    mapping: HashMap<String, String>,
+    validation_report: ValidationReport,
+    // Keys of the cached batches following the unfiltered batch
+    queries: Vec<String>,
+    filtered_queries: Vec<String>,
+}
+
+// Arrow IPC stream of one batch
+fn write_batch(batch: &RecordBatch) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    {
+        let mut writer =
+            StreamWriter::try_new(&mut buffer, &batch.schema()).map_err(|err| err.to_string())?;
+        writer.write(batch).map_err(|err| err.to_string())?;
+        writer.finish().map_err(|err| err.to_string())?;
+    }
+    Ok(buffer)
+}
+
+fn read_batch(bytes: &[u8]) -> Result<RecordBatch, String> {
+    let mut reader = StreamReader::try_new(Cursor::new(bytes)).map_err(|err| err.to_string())?;
+    match reader.next() {
+        Some(batch) => batch.map_err(|err| err.to_string()),
+        None => Err("Session contains an empty batch".to_string()),
+    }
+}
+
+// Sections are prefixed by their length
+fn write_section(session: &mut Vec<u8>, section: &[u8]) {
+    session.extend_from_slice(&(section.len() as u64).to_le_bytes());
+    session.extend_from_slice(section);
+}
+
+fn read_section<'a>(session: &'a [u8], offset: &mut usize) -> Result<&'a [u8], String> {
+    let truncated = || "Session is truncated".to_string();
+    let length_bytes = session.get(*offset..*offset + 8).ok_or_else(truncated)?;
+    let length = u64::from_le_bytes(length_bytes.try_into().unwrap()) as usize;
+    let section = session
+        .get(*offset + 8..*offset + 8 + length)
+        .ok_or_else(truncated)?;
+    *offset += 8 + length;
+    Ok(section)
+}
+
+fn cached_batches(
+    cache: &HashMap<String, RecordBatch>,
+) -> Result<(Vec<String>, Vec<Vec<u8>>), String> {
+    let mut keys = Vec::new();
+    let mut batches = Vec::new();
+    for (key, batch) in cache {
+        keys.push(key.to_owned());
+        batches.push(write_batch(batch)?);
+    }
+    Ok((keys, batches))
+}
+
+// Layout: magic, version, header (JSON), unfiltered batch, query summary
+// and cached batches (Arrow IPC)
+pub fn export_session(include_caches: bool) -> Result<Vec<u8>, String> {
+    let batch = get_unfiltered_record_batch().ok_or("No profile loaded".to_string())?;
+    let dict = get_serde_dict().ok_or("No profile loaded".to_string())?;
+
+    let (queries, query_batches) = if include_caches {
+        cached_batches(&get_query_from_cache().lock().unwrap())?
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let (filtered_queries, filter_batches) = if include_caches {
+        cached_batches(&get_filter_query_from_cache().lock().unwrap())?
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    let header = SessionHeader {
+        archive_queries: get_archive_queries()
+            .iter()
+            .map(|query| query.name.to_owned())
+            .collect(),
+        selected_query: get_selected_query(),
+        dict: (*dict).clone(),
+        operator_table: get_operator_table().lock().unwrap().to_owned(),
+        mapping: get_mapping_operator().lock().unwrap().to_owned(),
+        validation_report: get_validation_report().lock().unwrap().to_owned(),
+        queries: queries,
+        filtered_queries: filtered_queries,
+    };
+    let header = serde_json::to_vec(&header).map_err(|err| err.to_string())?;
+
+    let mut session = SESSION_MAGIC.to_vec();
+    session.extend_from_slice(&SESSION_VERSION.to_le_bytes());
+    write_section(&mut session, &header);
+    write_section(&mut session, &write_batch(&batch.batch)?);
+    // The other queries can't be loaded without the archive, their summary is kept
+    write_section(&mut session, &write_batch(&query_summary())?);
+    for batch in query_batches.iter().chain(&filter_batches) {
+        write_section(&mut session, batch);
+    }
+    Ok(session)
+}
+
+// Restores the profile of an exported session without the archive.
+// Returns the raw query plan for the frontend
+pub fn import_session(session: &[u8]) -> Result<String, String> {
+    let version_start = SESSION_MAGIC.len();
+    if session.len() < version_start + 4 || &session[..version_start] != SESSION_MAGIC {
+        return Err("Not a session".to_string());
+    }
+    let version = u32::from_le_bytes(session[version_start..version_start + 4].try_into().unwrap());
+    if version != SESSION_VERSION {
+        return Err(format!(
+            "Session version {} is not supported, expected {}",
+            version, SESSION_VERSION
+        ));
+    }
+
+    let mut offset = version_start + 4;
+    let header: SessionHeader = serde_json::from_slice(read_section(session, &mut offset)?)
+        .map_err(|err| err.to_string())?;
+    let batch = read_batch(read_section(session, &mut offset)?)?;
+    let summary = read_batch(read_section(session, &mut offset)?)?;
+    if header.selected_query >= header.archive_queries.len() {
+        return Err("Session has no selected query".to_string());
+    }
+
+    let mut queries = HashMap::new();
+    for key in &header.queries {
+        queries.insert(key.to_owned(), read_batch(read_section(session, &mut offset)?)?);
+    }
+    let mut filtered_queries = HashMap::new();
+    for key in &header.filtered_queries {
+        filtered_queries.insert(key.to_owned(), read_batch(read_section(session, &mut offset)?)?);
+    }
+
+    // Nothing of the previous profile may remain, the archive isn't available anymore.
+    // Without a file size selecting another query fails
+    clear_cache();
+    clear_entry_buffers();
+    reset_file_size();
+    set_archive_queries(
+        header
+            .archive_queries
+            .into_iter()
+            .map(|name| QueryFiles {
+                name,
+                ..QueryFiles::default()
+            })
+            .collect(),
+    );
+    set_selected_query(header.selected_query);
+    set_query_summary(summary);
+
+    let mut dict = header.dict;
+    dict.query_plan = QueryPlan::parse(&dict.query_plan_json);
+    let query_plan_json = dict.query_plan_json.to_owned();
+    set_serde_dict(dict);
+    set_operator_table(header.operator_table);
+    insert_mapping_hashmap(header.mapping);
+    set_validation_report(header.validation_report);
+    set_sample_record_batch(build_sample(&batch));
+    set_summary_cube(build_summary_cube(&batch));
+    set_unfiltered_record_batch(batch);
+    get_query_from_cache().lock().unwrap().extend(queries);
+    get_filter_query_from_cache().lock().unwrap().extend(filtered_queries);
+
+    Ok(query_plan_json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exec::basic::queries::query_list,
+        state::state::{get_file_size, get_query_summary, set_file_size},
+        utils::{
+            array_util::{get_int32_column, get_stringarray_column},
+            test_util::profile_batch,
+        },
+    };
+
+    static PLAN: &str = r#"{"operator": "groupby3", "input": {"operator": "tablescan1"}}"#;
+
+    // Second of three queries of an archive, with a cached result and summary
+    fn load_profile() -> RecordBatch {
+        let batch = profile_batch(100);
+        set_file_size(1000);
+        set_archive_queries(
+            ["q1", "q2", "q3"]
+                .iter()
+                .map(|name| QueryFiles {
+                    name: name.to_string(),
+                    ..QueryFiles::default()
+                })
+                .collect(),
+        );
+        set_selected_query(1);
+        // Stands in for the summary read from the archive
+        set_query_summary(profile_batch(3));
+        set_serde_dict(SerdeDict {
+            dict: HashMap::new(),
+            query_plan: QueryPlan::parse(PLAN),
+            uir_program: Default::default(),
+            mapping: HashMap::new(),
+            query_plan_json: PLAN.to_string(),
+        });
+        set_unfiltered_record_batch(batch.clone());
+        get_query_from_cache()
+            .lock()
+            .unwrap()
+            .insert("count?operator".to_string(), profile_batch(4));
+        batch
+    }
+
+    fn load_other_profile() {
+        set_file_size(10);
+        set_archive_queries(vec![QueryFiles::default()]);
+        set_unfiltered_record_batch(profile_batch(10));
+        clear_cache();
+    }
+
+    #[test]
+    fn session_restores_the_profile_and_the_queries() {
+        let batch = load_profile();
+        let session = export_session(true).unwrap();
+        load_other_profile();
+
+        assert_eq!(import_session(&session).unwrap(), PLAN);
+        assert_eq!(get_unfiltered_record_batch().unwrap().batch, batch);
+        assert!(get_serde_dict().unwrap().query_plan.is_some());
+        assert_eq!(
+            get_query_from_cache().lock().unwrap().get("count?operator"),
+            Some(&profile_batch(4))
+        );
+        assert_eq!(get_file_size(), None);
+
+        let queries = query_list();
+        let names = get_stringarray_column(&queries, 0);
+        let selected = get_int32_column(&queries, 1);
+        assert_eq!(names.iter().flatten().collect::<Vec<_>>(), ["q1", "q2", "q3"]);
+        assert_eq!(selected.values(), &[0, 1, 0]);
+        assert_eq!(get_query_summary(), Some(profile_batch(3)));
+        assert_eq!(query_summary(), profile_batch(3));
+
+        // Without the archive the summary can't be computed again
+        set_archive_queries(get_archive_queries());
+        assert_eq!(query_summary().schema().field(0).name(), "error");
+    }
+
+    #[test]
+    fn caches_are_only_exported_on_request() {
+        load_profile();
+        let session = export_session(false).unwrap();
+        load_other_profile();
+
+        import_session(&session).unwrap();
+        assert!(get_query_from_cache().lock().unwrap().is_empty());
+        assert_eq!(get_unfiltered_record_batch().unwrap().batch.num_rows(), 100);
+    }
+
+    #[test]
+    fn invalid_sessions_keep_the_loaded_profile() {
+        load_profile();
+        let session = export_session(true).unwrap();
+        load_other_profile();
+
+        let mut other_version = session.clone();
+        other_version[SESSION_MAGIC.len()] += 1;
+        let invalid = [
+            b"UMBRAPERF".to_vec(),
+            session[1..].to_vec(),
+            other_version,
+            session[..session.len() - 1].to_vec(),
+        ];
+        for session in &invalid {
+            assert!(import_session(session).is_err());
+            assert_eq!(get_unfiltered_record_batch().unwrap().batch.num_rows(), 10);
+            assert_eq!(get_file_size(), Some(10));
+        }
+    }
+}
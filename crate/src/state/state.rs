@@ -6,25 +6,48 @@ use std::{
 
 use arrow::record_batch::RecordBatch;
 
-use crate::web_file::serde_reader::SerdeDict;
+use crate::{
+    exec::basic::{op_mapping::operator_hashmap, summary_cube::SummaryCube},
+    web_file::{archive::QueryFiles, serde_reader::SerdeDict, validation::ValidationReport},
+};
 
 pub struct RecordBatchShared {
     pub batch: RecordBatch,
 }
 
+// Stratified sample of the unfiltered batch for approximate answers
+pub struct SampleBatch {
+    pub batch: RecordBatch,
+    // Share of the samples taken and number of samples of the unfiltered batch
+    pub rate: f64,
+    pub population: f64,
+}
+
 //STATE STRUCT
 pub struct State {
     // Batch State
     pub unfiltered_record_batch: Option<Arc<RecordBatchShared>>,
     pub swimlane_batch: Option<Arc<RecordBatchShared>>,
+    pub sample_batch: Option<Arc<SampleBatch>>,
+    pub summary_cube: Option<Arc<SummaryCube>>,
     // Caching for queries
     pub queries: Arc<Mutex<HashMap<String, RecordBatch>>>,
     pub filtered_queries: Arc<Mutex<HashMap<String, RecordBatch>>>,
     // Mapping: Op <-> "Nice" Op
     pub mapping: Arc<Mutex<HashMap<String, String>>>,
+    // Operator kind <-> symbol
+    pub operator_table: Arc<Mutex<HashMap<String, String>>>,
+    // Symbols supplied by the user, they outlast the archive
+    pub user_operator_table: HashMap<String, String>,
     pub dict: Option<Arc<SerdeDict>>,
-    // File Loading
-    pub parquet_file_binary: Arc<Mutex<Vec<u8>>>,
+    pub validation_report: Arc<Mutex<ValidationReport>>,
+    // Queries of the archive and the loaded one
+    pub archive_queries: Vec<QueryFiles>,
+    pub selected_query: usize,
+    // Per-query totals of the archive, computed once
+    pub query_summary: Option<RecordBatch>,
+    // File Loading: decompressed archive entries per file name
+    pub entry_buffers: HashMap<String, Arc<Vec<u8>>>,
     pub file_size: Option<u64>,
 }
 
@@ -33,14 +56,24 @@ thread_local! {
         // Batch State
         unfiltered_record_batch: None,
         swimlane_batch: None,
+        sample_batch: None,
+        summary_cube: None,
         // Caching for queries
         queries:  Arc::new(Mutex::new(HashMap::new())),
         filtered_queries: Arc::new(Mutex::new(HashMap::new())),
         // Mapping: Op <-> "Nice" Op
         mapping:  Arc::new(Mutex::new(HashMap::new())),
+        // Operator kind <-> symbol
+        operator_table: Arc::new(Mutex::new(operator_hashmap())),
+        user_operator_table: HashMap::new(),
         dict: None,
-        // File Loading
-        parquet_file_binary: Arc::new(Mutex::new(Vec::new())),
+        validation_report: Arc::new(Mutex::new(ValidationReport::default())),
+        // Queries of the archive and the loaded one
+        archive_queries: vec![QueryFiles::default()],
+        selected_query: 0,
+        query_summary: None,
+        // File Loading: decompressed archive entries per file name
+        entry_buffers: HashMap::new(),
         file_size: None,
     });
 }
@@ -84,6 +117,29 @@ pub fn set_unfiltered_record_batch(record_batches: RecordBatch) {
     };
     _with_state_mut(|s| s.unfiltered_record_batch = Some(Arc::new(shared_record_batch)));
 }
+pub fn reset_unfiltered_record_batch() {
+    _with_state_mut(|s| {
+        s.unfiltered_record_batch = None;
+        s.sample_batch = None;
+        s.summary_cube = None;
+    });
+}
+
+// RECORD BATCH STATE - SAMPLE BATCH
+pub fn get_sample_record_batch() -> Option<Arc<SampleBatch>> {
+    with_state(|s| s.sample_batch.clone())
+}
+pub fn set_sample_record_batch(sample: Option<SampleBatch>) {
+    _with_state_mut(|s| s.sample_batch = sample.map(Arc::new));
+}
+
+// RECORD BATCH STATE - SUMMARY CUBE
+pub fn get_summary_cube() -> Option<Arc<SummaryCube>> {
+    with_state(|s| s.summary_cube.clone())
+}
+pub fn set_summary_cube(cube: Option<SummaryCube>) {
+    _with_state_mut(|s| s.summary_cube = cube.map(Arc::new));
+}
 
 // MAPPING STATE
 pub fn get_mapping_operator() -> Arc<Mutex<HashMap<String, String>>> {
@@ -96,36 +152,85 @@ pub fn insert_mapping_hashmap(hashmap: HashMap<String, String>) {
     });
 }
 
+pub fn get_operator_table() -> Arc<Mutex<HashMap<String, String>>> {
+    with_state(|s| s.operator_table.clone())
+}
+pub fn set_operator_table(table: HashMap<String, String>) {
+    _with_state_mut(|s| s.operator_table = Arc::new(Mutex::new(table)));
+}
+pub fn get_user_operator_table() -> HashMap<String, String> {
+    with_state(|s| s.user_operator_table.clone())
+}
+pub fn set_user_operator_table(table: HashMap<String, String>) {
+    _with_state_mut(|s| s.user_operator_table = table);
+}
+
 // BUFFER STATE
-pub fn get_buffer() -> Arc<Mutex<Vec<u8>>> {
-    with_state(|s| s.parquet_file_binary.clone())
+pub fn get_entry_buffer(file_name: &str) -> Option<Arc<Vec<u8>>> {
+    with_state(|s| s.entry_buffers.get(file_name).cloned())
 }
-pub fn append_to_buffer(mut vec: Vec<u8>) {
+pub fn insert_entry_buffer(file_name: &str, buffer: Arc<Vec<u8>>) {
     _with_state_mut(|s| {
-        let mut binary = s.parquet_file_binary.lock().unwrap();
-        binary.append(&mut vec);
+        s.entry_buffers.insert(file_name.to_string(), buffer);
     });
 }
-pub fn clear_buffer() {
-    _with_state_mut(|s| {
-        s.parquet_file_binary = Arc::new(Mutex::new(Vec::new()));
-    });
+pub fn clear_entry_buffers() {
+    _with_state_mut(|s| s.entry_buffers.clear());
 }
 
 // READER STATE
 pub fn get_serde_dict() -> Option<Arc<SerdeDict>> {
     with_state(|s| s.dict.clone())
 }
-pub fn _get_file_size() -> Option<u64> {
+pub fn get_file_size() -> Option<u64> {
     with_state(|s| s.file_size.clone())
 }
 pub fn set_file_size(file_size: u64) {
     _with_state_mut(|s| s.file_size = Some(file_size));
 }
+pub fn reset_file_size() {
+    _with_state_mut(|s| s.file_size = None);
+}
 pub fn set_serde_dict(serde_dict: SerdeDict) {
     _with_state_mut(|s| s.dict = Some(Arc::new(serde_dict)));
 }
 
+// VALIDATION STATE
+pub fn get_validation_report() -> Arc<Mutex<ValidationReport>> {
+    with_state(|s| s.validation_report.clone())
+}
+pub fn set_validation_report(report: ValidationReport) {
+    _with_state_mut(|s| s.validation_report = Arc::new(Mutex::new(report)));
+}
+
+// ARCHIVE STATE
+pub fn get_archive_queries() -> Vec<QueryFiles> {
+    with_state(|s| s.archive_queries.clone())
+}
+pub fn set_archive_queries(queries: Vec<QueryFiles>) {
+    _with_state_mut(|s| {
+        s.archive_queries = queries;
+        s.selected_query = 0;
+        s.query_summary = None;
+    });
+}
+pub fn get_query_summary() -> Option<RecordBatch> {
+    with_state(|s| s.query_summary.clone())
+}
+pub fn set_query_summary(query_summary: RecordBatch) {
+    _with_state_mut(|s| s.query_summary = Some(query_summary));
+}
+pub fn get_selected_query() -> usize {
+    with_state(|s| s.selected_query)
+}
+pub fn set_selected_query(selected_query: usize) {
+    _with_state_mut(|s| s.selected_query = selected_query);
+}
+// Files of the loaded query
+pub fn get_query_files() -> QueryFiles {
+    with_state(|s| s.archive_queries[s.selected_query].clone())
+}
+
 // CACHE STATE
 pub fn clear_cache() {
     _with_state_mut(|s| {
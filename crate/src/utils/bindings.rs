@@ -14,6 +14,16 @@ extern "C" {
     #[wasm_bindgen(js_name = "sendJsQueryResult")]
     pub fn send_js_query_result(query_result: Vec<u8>);
 
+    // Chunked results: schema, slices with sequence number, end marker with the number of slices
+    #[wasm_bindgen(js_name = "sendJsResultSchema")]
+    pub fn send_js_result_schema(schema: Vec<u8>);
+
+    #[wasm_bindgen(js_name = "sendJsResultChunk")]
+    pub fn send_js_result_chunk(sequence: i32, chunk: Vec<u8>);
+
+    #[wasm_bindgen(js_name = "sendJsResultEnd")]
+    pub fn send_js_result_end(chunks: i32);
+
     #[wasm_bindgen(js_name = "notifyJsQueryPlan")]
     pub fn send_js_query_plan(query_plan: String);
 
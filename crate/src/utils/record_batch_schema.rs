@@ -1,7 +1,8 @@
+// Optional columns (tid, cpu) follow if present in the profile and are found by name
 pub enum RecordBatchSchema {
     Operator = 0,
     EvName = 1,
-    _Time = 2,
+    Time = 2,
     Pipeline = 3,
     _Addr = 4,
     Uri = 5,
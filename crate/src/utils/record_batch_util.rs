@@ -1,18 +1,23 @@
 use crate::{
-    bindings::send_js_query_result,
-    state::state::get_serde_dict,
-    web_file::{serde_reader::DictFields, web_file_chunkreader::WebFileChunkReader}, exec::basic::{basic::sort_batch, filter::filter_with},
+    bindings::{send_js_query_result, send_js_result_chunk, send_js_result_end, send_js_result_schema},
+    state::state::{get_query_files, get_serde_dict},
+    web_file::{
+        serde_reader::DictFields,
+        validation::{add_validation_issue, Severity},
+        web_file_chunkreader::WebFileChunkReader,
+    }, exec::basic::{basic::{select_columns, sort_batch}, dso::derive_dso_column, filter::filter_with},
 };
 use arrow::{
-    array::{Array, ArrayRef, Float64Array, Int64Array, StringArray, UInt64Array},
+    array::{make_array, Array, ArrayRef, BinaryArray, MutableArrayData, Float64Array, Int32Array, Int64Array, StringArray, UInt64Array},
+    compute::cast,
     datatypes::{DataType, Field, Schema, SchemaRef},
-    record_batch::RecordBatch,
+    record_batch::{RecordBatch, RecordBatchReader},
 };
 use parquet::{
     arrow::{arrow_reader::ParquetRecordBatchReader, ArrowReader, ParquetFileArrowReader},
-    file::serialized_reader::SerializedFileReader,
+    file::{reader::ChunkReader, serialized_reader::SerializedFileReader},
 };
-use std::{io::Cursor, sync::Arc, collections::HashSet, iter::FromIterator};
+use std::{io::Cursor, sync::Arc, collections::{HashMap, HashSet}, iter::FromIterator};
 
 use super::array_util::{get_floatarray_column, get_int64_column, get_uint_column};
 
@@ -24,25 +29,83 @@ fn flatten<T>(nested: Vec<Vec<T>>) -> Vec<T> {
     nested.into_iter().flatten().collect()
 }
 
+// Value for ids which are missing in the dictionary
+pub static UNKNOWN_DICT_KEY: &str = "unknown";
+
+// Columns of samples.parquet which are always read
+static SAMPLE_COLUMNS: [usize; 8] = [0, 1, 2, 3, 10, 6, 13, 14];
+// Columns of samples.parquet which are read if present
+static OPTIONAL_SAMPLE_COLUMNS: [&str; 3] = ["tid", "cpu", "dso"];
+
 // Parquet Reader, specify columns which are read
-pub fn init_reader(file_size: i32) -> ParquetRecordBatchReader {
-    let webfile_chunkreader = WebFileChunkReader::new(file_size as i32);
-    let reader = SerializedFileReader::new(webfile_chunkreader).unwrap();
+// Returns the reader and the positions to bring the read columns in the order of RecordBatchSchema,
+// optional columns are appended
+fn init_reader<R: ChunkReader + 'static>(
+    chunk_reader: R,
+) -> Result<(ParquetRecordBatchReader, Vec<usize>), String> {
+    let reader = SerializedFileReader::new(chunk_reader).map_err(|err| err.to_string())?;
     let mut reader = ParquetFileArrowReader::new(Arc::new(reader));
+
+    let schema = reader.get_schema().map_err(|err| err.to_string())?;
+    let mut columns = SAMPLE_COLUMNS.to_vec();
+    let mut optional_columns = Vec::new();
+    for name in OPTIONAL_SAMPLE_COLUMNS.iter() {
+        if let Ok(index) = schema.index_of(name) {
+            columns.push(index);
+            optional_columns.push(index);
+        }
+    }
+
+    // The reader returns the columns in the order of the file
+    let mut file_order = columns.clone();
+    file_order.sort();
+    let mut sample_columns = SAMPLE_COLUMNS.to_vec();
+    sample_columns.sort();
+    let positions = sample_columns
+        .iter()
+        .chain(optional_columns.iter())
+        .map(|column| file_order.iter().position(|x| x == column).unwrap())
+        .collect::<Vec<usize>>();
+
     let record_reader = reader
-        .get_record_reader_by_columns(vec![0, 1, 2, 3, 10, 6, 13, 14].into_iter(), 1024 * 8)
-        .unwrap();
-    record_reader
+        .get_record_reader_by_columns(columns.into_iter(), 1024 * 8)
+        .map_err(|err| err.to_string())?;
+    Ok((record_reader, positions))
+}
+
+// Record is read in batches, a file without samples gives one empty batch
+pub fn init_record_batches(file_size: i32) -> Option<Vec<RecordBatch>> {
+    read_record_batches(WebFileChunkReader::new(file_size as i32))
 }
 
-// Record is read in batches
-pub fn init_record_batches(file_size: i32) -> Vec<RecordBatch> {
-    let mut record_reader = init_reader(file_size);
+// None if the samples file is corrupt or truncated, the error is added to the validation report
+fn read_record_batches<R: ChunkReader + 'static>(chunk_reader: R) -> Option<Vec<RecordBatch>> {
+    let invalid = |err: String| {
+        add_validation_issue(
+            Severity::Error,
+            &get_query_files().samples,
+            format!("Invalid samples file: {}", err),
+            1,
+        );
+        None
+    };
+
+    let (mut record_reader, positions) = match init_reader(chunk_reader) {
+        Ok(reader) => reader,
+        Err(err) => return invalid(err),
+    };
     let mut vec = Vec::new();
     while let Some(record) = record_reader.next() {
-        vec.push(record.unwrap());
+        match record {
+            Ok(record) => vec.push(select_columns(record, positions.to_owned())),
+            Err(err) => return invalid(err.to_string()),
+        }
+    }
+    if vec.is_empty() {
+        let empty = RecordBatch::new_empty(record_reader.schema());
+        vec.push(select_columns(empty, positions));
     }
-    vec
+    Some(vec)
 }
 
 // Combine multiple record batches to one
@@ -64,6 +127,55 @@ pub fn combine_to_one_record_batch(vec_batch: Vec<RecordBatch>) -> RecordBatch {
     batch.unwrap()
 }
 
+// Results of sub-queries as one batch with a row per sub-query: its position (query_index),
+// the sub-query (query) and its result as Arrow IPC stream (result)
+pub fn sub_query_container(vec_batch: &[RecordBatch], queries: &[&str]) -> RecordBatch {
+    let results = vec_batch.iter().map(encode_ipc_stream).collect::<Vec<Vec<u8>>>();
+    create_new_record_batch(
+        vec!["query_index", "query", "result"],
+        vec![DataType::Int32, DataType::Utf8, DataType::Binary],
+        vec![
+            Arc::new(Int32Array::from((0..queries.len() as i32).collect::<Vec<i32>>())),
+            Arc::new(StringArray::from(queries.to_vec())),
+            Arc::new(BinaryArray::from(
+                results.iter().map(|result| result.as_slice()).collect::<Vec<&[u8]>>(),
+            )),
+        ],
+    )
+}
+
+// Names of numbered columns, e.g. perc1, ..., percN for the shares of the N events
+pub fn numbered_column_names(prefix: &str, num_of_columns: usize) -> Vec<String> {
+    (1..=num_of_columns)
+        .map(|i| format!("{}{}", prefix, i))
+        .collect()
+}
+
+// Appends a Float64 column per name to the fields of a new record batch
+pub fn push_float_columns<'a>(
+    names: &'a [String],
+    values: Vec<Vec<f64>>,
+    field_names: &mut Vec<&'a str>,
+    data_types: &mut Vec<DataType>,
+    columns: &mut Vec<ArrayRef>,
+) {
+    for (name, values) in names.iter().zip(values) {
+        field_names.push(name.as_str());
+        data_types.push(DataType::Float64);
+        columns.push(Arc::new(Float64Array::from(values)));
+    }
+}
+
+// Result of a query which can't be answered, e.g. because of invalid parameters,
+// one row with the reason (error)
+pub fn error_record_batch(message: &str) -> RecordBatch {
+    create_new_record_batch(
+        vec!["error"],
+        vec![DataType::Utf8],
+        vec![Arc::new(StringArray::from(vec![message]))],
+    )
+}
+
 // Creating a new record batch, this method simplfies record batch creation
 pub fn create_new_record_batch(
     field_names: Vec<&str>,
@@ -82,8 +194,48 @@ pub fn create_new_record_batch(
     return batch;
 }
 
-// Converts Vec<RecordBatch> to one whole RecordBatch
+// Columns of the mapped profile in the order of RecordBatchSchema, without the optional ones
+fn profile_fields() -> (Vec<&'static str>, Vec<DataType>) {
+    (
+        vec![
+            "operator",
+            "ev_name",
+            "time",
+            "pipeline",
+            "addr",
+            "uri",
+            "op_ext",
+            "physical_op",
+        ],
+        vec![
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Utf8,
+            DataType::UInt64,
+            DataType::Int64,
+            DataType::Utf8,
+            DataType::Utf8,
+        ],
+    )
+}
+
+// Profile without samples
+fn empty_profile() -> RecordBatch {
+    let (field_names, data_types) = profile_fields();
+    let fields = field_names
+        .iter()
+        .zip(data_types)
+        .map(|(name, data_type)| Field::new(name, data_type, false))
+        .collect::<Vec<Field>>();
+    RecordBatch::new_empty(Arc::new(Schema::new(fields)))
+}
+
+// Converts Vec<RecordBatch> to one whole RecordBatch, without batches the profile is empty
 pub fn convert(batches: Vec<RecordBatch>) -> RecordBatch {
+    if batches.is_empty() {
+        return empty_profile();
+    }
     let number_columns = batches[0].num_columns() as i32;
     let mut to_concat_array = Vec::new();
 
@@ -106,6 +258,9 @@ pub fn convert(batches: Vec<RecordBatch>) -> RecordBatch {
 }
 
 pub fn convert_without_mapping(batches: Vec<RecordBatch>) -> RecordBatch {
+    if batches.is_empty() {
+        return empty_profile();
+    }
     let number_columns = batches[0].num_columns() as i32;
     let mut to_concat_array = Vec::new();
 
@@ -126,6 +281,20 @@ pub fn convert_without_mapping(batches: Vec<RecordBatch>) -> RecordBatch {
     create_record_batch(batches[0].schema(), columns)
 }
 
+// Dictionary lookup, ids missing in the dictionary are counted and mapped to "unknown"
+fn lookup<'a>(hash_map: &'a HashMap<u64, String>, value: i64, unknown: &mut u64) -> &'a str {
+    match hash_map.get(&(value as u64)) {
+        Some(dict_key) => dict_key.as_str(),
+        None => {
+            *unknown += 1;
+            UNKNOWN_DICT_KEY
+        }
+    }
+}
+
+// Samples of this operator are not part of the loaded profile
+pub static EXCLUDED_OPERATOR: &str = "analyzeplan1";
+
 pub fn apply_mapping_to_record_batch(batch: RecordBatch) -> RecordBatch {
     let serde = get_serde_dict().unwrap();
 
@@ -133,20 +302,18 @@ pub fn apply_mapping_to_record_batch(batch: RecordBatch) -> RecordBatch {
     let operator_col = get_int64_column(&batch, 0);
     let mut operator_vec = Vec::new();
     let hash_map = serde.dict.get(&(DictFields::Operator as i64)).unwrap();
+    let mut unknown_operators = 0;
     for value in operator_col {
-        let value = &(value.unwrap() as u64);
-        let dict_key = hash_map.get(value);
-
-        operator_vec.push(dict_key.unwrap().as_str());
+        operator_vec.push(lookup(hash_map, value.unwrap(), &mut unknown_operators));
     }
 
     // Event
     let event_nam = get_int64_column(&batch, 3);
     let mut event_vec = Vec::new();
     let hash_map = serde.dict.get(&(DictFields::Event as i64)).unwrap();
+    let mut unknown_events = 0;
     for value in event_nam {
-        let dict_key = hash_map.get(&(value.unwrap() as u64));
-        event_vec.push(dict_key.unwrap().as_str());
+        event_vec.push(lookup(hash_map, value.unwrap(), &mut unknown_events));
     }
 
     // Time
@@ -160,9 +327,9 @@ pub fn apply_mapping_to_record_batch(batch: RecordBatch) -> RecordBatch {
     let pipeline = get_int64_column(&batch, 1);
     let mut pipeline_vec = Vec::new();
     let hash_map = serde.dict.get(&(DictFields::Pipeline as i64)).unwrap();
+    let mut unknown_pipelines = 0;
     for value in pipeline {
-        let dict_key = hash_map.get(&(value.unwrap() as u64));
-        pipeline_vec.push(dict_key.unwrap().as_str());
+        pipeline_vec.push(lookup(hash_map, value.unwrap(), &mut unknown_pipelines));
     }
 
     // Address
@@ -183,81 +350,279 @@ pub fn apply_mapping_to_record_batch(batch: RecordBatch) -> RecordBatch {
     let op_ext_col = get_int64_column(&batch, 6);
     let mut op_extension = Vec::new();
     let hash_map = serde.dict.get(&(DictFields::OpExtension as i64)).unwrap();
+    let mut unknown_op_extensions = 0;
     for value in op_ext_col {
-        let dict_key = hash_map.get(&(value.unwrap() as u64));
-        op_extension.push(dict_key.unwrap().as_str());
+        op_extension.push(lookup(hash_map, value.unwrap(), &mut unknown_op_extensions));
     }
 
     // Physical operation
     let pyhs_op_col = get_int64_column(&batch, 7);
     let mut physical_op = Vec::new();
     let hash_map = serde.dict.get(&(DictFields::PhysicalOp as i64)).unwrap();
+    let mut unknown_physical_ops = 0;
     for value in pyhs_op_col {
-        let dict_key = hash_map.get(&(value.unwrap() as u64));
-        physical_op.push(dict_key.unwrap().as_str());
+        physical_op.push(lookup(hash_map, value.unwrap(), &mut unknown_physical_ops));
     }
 
-    let batch = create_new_record_batch(
-        vec![
-            "operator",
-            "ev_name",
-            "time",
-            "pipeline",
-            "addr",
-            "uri",
-            "op_ext",
-            "physical_op",
-        ],
-        vec![
-            DataType::Utf8,
-            DataType::Utf8,
-            DataType::Float64,
-            DataType::Utf8,
-            DataType::UInt64,
-            DataType::Int64,
-            DataType::Utf8,
-            DataType::Utf8,
-        ],
-        vec![
-            Arc::new(StringArray::from(operator_vec.clone())),
-            Arc::new(StringArray::from(event_vec)),
-            Arc::new(Float64Array::from(time)),
-            Arc::new(StringArray::from(pipeline_vec)),
-            Arc::new(UInt64Array::from(addr)),
-            Arc::new(Int64Array::from(uri)),
-            Arc::new(StringArray::from(op_extension)),
-            Arc::new(StringArray::from(physical_op)),
-        ],
-    );
+    for (field, unknown) in [
+        ("operators", unknown_operators),
+        ("events", unknown_events),
+        ("pipelines", unknown_pipelines),
+        ("op_extension", unknown_op_extensions),
+        ("physical_op", unknown_physical_ops),
+    ]
+    .iter()
+    {
+        if *unknown > 0 {
+            add_validation_issue(
+                Severity::Warning,
+                &get_query_files().dictionary,
+                format!("Samples with ids missing in dictionary section {}", field),
+                *unknown,
+            );
+        }
+    }
+
+    let (mut field_names, mut data_types) = profile_fields();
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(operator_vec.clone())),
+        Arc::new(StringArray::from(event_vec)),
+        Arc::new(Float64Array::from(time)),
+        Arc::new(StringArray::from(pipeline_vec)),
+        Arc::new(UInt64Array::from(addr)),
+        Arc::new(Int64Array::from(uri)),
+        Arc::new(StringArray::from(op_extension)),
+        Arc::new(StringArray::from(physical_op)),
+    ];
+
+    // Optional columns (tid, cpu) are used like pipelines, hence as strings,
+    // dso ids are mapped with the dictionary
+    let schema = batch.schema();
+    let mut unknown_dsos = 0;
+    for i in field_names.len()..batch.num_columns() {
+        let name = schema.field(i).name();
+        field_names.push(name);
+        data_types.push(DataType::Utf8);
+        if name == "dso" {
+            let hash_map = serde.dict.get(&(DictFields::Dso as i64)).unwrap();
+            let ids = cast(batch.column(i), &DataType::Int64).unwrap();
+            let dso = ids
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .into_iter()
+                .map(|value| lookup(hash_map, value.unwrap(), &mut unknown_dsos))
+                .collect::<Vec<&str>>();
+            columns.push(Arc::new(StringArray::from(dso)));
+        } else {
+            columns.push(cast(batch.column(i), &DataType::Utf8).unwrap());
+        }
+    }
+    // Profiles without dso column get it derived from the srclines
+    if schema.index_of("dso").is_err() {
+        let srclines = serde.dict.get(&(DictFields::Srcline as i64)).unwrap();
+        field_names.push("dso");
+        data_types.push(DataType::Utf8);
+        columns.push(Arc::new(StringArray::from(derive_dso_column(get_int64_column(&batch, 4), srclines))));
+    }
+    if unknown_dsos > 0 {
+        add_validation_issue(
+            Severity::Warning,
+            &get_query_files().dictionary,
+            "Samples with ids missing in dictionary section dso".to_string(),
+            unknown_dsos,
+        );
+    }
+
+    let batch = create_new_record_batch(field_names, data_types, columns);
 
     let mut op_unique: HashSet<&str> = HashSet::from_iter(operator_vec);
-    op_unique.remove("analyzeplan1");
+    op_unique.remove(EXCLUDED_OPERATOR);
     let hashset = Vec::from_iter(op_unique);
     return filter_with(0, hashset, &batch);
 }
 
-// Sending record batch to javascript via IPC which include a schema and a message
-pub fn send_record_batch_to_js(record_batch: &RecordBatch) {
-    let mut buff = Cursor::new(vec![]);
+// Results with more rows are streamed to javascript in slices of this size
+pub static RESULT_CHUNK_ROWS: usize = 65536;
 
-    let options = arrow::ipc::writer::IpcWriteOptions::default();
-    let mut dict = arrow::ipc::writer::DictionaryTracker::new(true);
+// Dictionaries of dictionary-encoded columns not sent before, followed by the batch
+fn encode_batch(
+    record_batch: &RecordBatch,
+    dict: &mut arrow::ipc::writer::DictionaryTracker,
+    options: &arrow::ipc::writer::IpcWriteOptions,
+) -> Vec<u8> {
+    let mut buff = Cursor::new(vec![]);
+    let (encoded_dictionaries, encoded_message) = arrow::ipc::writer::IpcDataGenerator::encoded_batch(
+        &arrow::ipc::writer::IpcDataGenerator::default(),
+        record_batch,
+        dict,
+        options,
+    )
+    .unwrap();
+
+    for encoded_dictionary in encoded_dictionaries {
+        let _writer_dict = arrow::ipc::writer::write_message(&mut buff, encoded_dictionary, options);
+    }
+    let _writer_mess = arrow::ipc::writer::write_message(&mut buff, encoded_message, options);
+    buff.into_inner()
+}
 
+fn encode_schema(
+    record_batch: &RecordBatch,
+    options: &arrow::ipc::writer::IpcWriteOptions,
+) -> Vec<u8> {
+    let mut buff = Cursor::new(vec![]);
     let encoded_schema = arrow::ipc::writer::IpcDataGenerator::schema_to_bytes(
         &arrow::ipc::writer::IpcDataGenerator::default(),
         &record_batch.schema(),
-        &options,
-    );
-    let encoded_message = arrow::ipc::writer::IpcDataGenerator::encoded_batch(
-        &arrow::ipc::writer::IpcDataGenerator::default(),
-        &record_batch,
-        &mut dict,
-        &options,
+        options,
     );
+    let _writer_schema = arrow::ipc::writer::write_message(&mut buff, encoded_schema, options);
+    buff.into_inner()
+}
+
+// Sending record batch to javascript via IPC which include a schema and a message
+pub fn send_record_batch_to_js(record_batch: &RecordBatch) {
+    if record_batch.num_rows() > RESULT_CHUNK_ROWS {
+        stream_record_batch_to_js(record_batch);
+        return;
+    }
+
+    send_js_query_result(encode_ipc_stream(record_batch));
+}
+
+// Schema and batch as one IPC stream
+fn encode_ipc_stream(record_batch: &RecordBatch) -> Vec<u8> {
+    let options = arrow::ipc::writer::IpcWriteOptions::default();
+    let mut dict = arrow::ipc::writer::DictionaryTracker::new(true);
+
+    let mut buff = encode_schema(record_batch, &options);
+    buff.extend(encode_batch(record_batch, &mut dict, &options));
+    buff
+}
+
+// Rows offset..offset + length in new arrays, the IPC writer ignores the offsets of sliced arrays
+fn copy_rows(record_batch: &RecordBatch, offset: usize, length: usize) -> RecordBatch {
+    let columns = record_batch
+        .columns()
+        .iter()
+        .map(|column| {
+            let mut data = MutableArrayData::new(vec![column.data()], false, length);
+            data.extend(0, offset, offset + length);
+            make_array(data.freeze())
+        })
+        .collect();
+    create_record_batch(record_batch.schema(), columns)
+}
+
+// Schema and the slices of chunk_rows rows, each slice with the dictionaries it references
+fn encode_chunks(record_batch: &RecordBatch, chunk_rows: usize) -> (Vec<u8>, Vec<Vec<u8>>) {
+    let options = arrow::ipc::writer::IpcWriteOptions::default();
+    let schema = encode_schema(record_batch, &options);
+
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    while offset < record_batch.num_rows() {
+        let length = chunk_rows.min(record_batch.num_rows() - offset);
+        let slice = copy_rows(record_batch, offset, length);
+        let mut dict = arrow::ipc::writer::DictionaryTracker::new(false);
+        chunks.push(encode_batch(&slice, &mut dict, &options));
+        offset += length;
+    }
+    (schema, chunks)
+}
+
+// Streams the schema, then slices of RESULT_CHUNK_ROWS rows with their sequence number
+// and finally the end marker with the number of slices.
+// Every slice carries the dictionaries it references, javascript reads it with the schema alone
+fn stream_record_batch_to_js(record_batch: &RecordBatch) {
+    let (schema, chunks) = encode_chunks(record_batch, RESULT_CHUNK_ROWS);
+    send_js_result_schema(schema);
+    let count = chunks.len() as i32;
+    for (sequence, chunk) in chunks.into_iter().enumerate() {
+        send_js_result_chunk(sequence as i32, chunk);
+    }
+    send_js_result_end(count);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exec::basic::{sample::build_sample, summary_cube::build_summary_cube},
+        state::state::{get_validation_report, set_validation_report},
+        utils::test_util::profile_batch,
+    };
+
+    #[test]
+    fn empty_profiles_are_loaded() {
+        assert_eq!(convert(Vec::new()).num_rows(), 0);
+        assert_eq!(convert_without_mapping(Vec::new()).num_rows(), 0);
+
+        let empty = profile_batch(0);
+        let batch = convert_without_mapping(vec![empty.clone()]);
+        assert_eq!(batch.num_rows(), 0);
+        assert_eq!(batch.schema(), empty.schema());
+        build_sample(&batch);
+        build_summary_cube(&batch);
+    }
+
+    // samples.parquet with rows samples, column i holds i for every sample
+    fn samples_file(rows: usize) -> Vec<u8> {
+        let names = numbered_column_names("column", 15);
+        let batch = create_new_record_batch(
+            names.iter().map(|name| name.as_str()).collect(),
+            vec![DataType::Int64; 15],
+            (0..15)
+                .map(|i| Arc::new(Int64Array::from(vec![i as i64; rows])) as ArrayRef)
+                .collect(),
+        );
+        let cursor = parquet::util::cursor::InMemoryWriteableCursor::default();
+        let mut writer =
+            parquet::arrow::ArrowWriter::try_new(cursor.clone(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        cursor.data()
+    }
+
+    fn read(bytes: Vec<u8>) -> Option<Vec<RecordBatch>> {
+        read_record_batches(parquet::util::cursor::SliceableCursor::new(bytes))
+    }
 
-    let _writer_schema = arrow::ipc::writer::write_message(&mut buff, encoded_schema, &options);
-    let _writer_mess =
-        arrow::ipc::writer::write_message(&mut buff, encoded_message.unwrap().1, &options);
+    #[test]
+    fn unreadable_samples_are_validation_errors() {
+        let batches = read(samples_file(100)).unwrap();
+        assert_eq!(batches[0].num_rows(), 100);
+        assert_eq!(get_int64_column(&batches[0], 3).value(0), 3);
+        assert!(get_validation_report().lock().unwrap().is_valid());
+
+        let file = samples_file(100);
+        let mut corrupt = file.clone();
+        for byte in &mut corrupt[4..file.len() / 2] {
+            *byte = 0xff;
+        }
+        for bytes in [b"not parquet".to_vec(), file[file.len() / 2..].to_vec(), corrupt] {
+            set_validation_report(Default::default());
+            assert!(read(bytes).is_none());
+            assert!(!get_validation_report().lock().unwrap().is_valid());
+        }
+    }
 
-    send_js_query_result(buff.into_inner());
+    #[test]
+    fn streamed_chunks_decode_to_the_batch() {
+        let batch = profile_batch(30);
+        let (schema, chunks) = encode_chunks(&batch, 7);
+        assert_eq!(chunks.len(), 5);
+
+        let stream = [vec![schema], chunks].concat().concat();
+        let reader = arrow::ipc::reader::StreamReader::try_new(Cursor::new(stream)).unwrap();
+        let mut offset = 0;
+        for chunk in reader {
+            let chunk = chunk.unwrap();
+            assert_eq!(chunk.schema(), batch.schema());
+            assert_eq!(chunk, batch.slice(offset, chunk.num_rows()));
+            offset += chunk.num_rows();
+        }
+        assert_eq!(offset, 30);
+    }
 }
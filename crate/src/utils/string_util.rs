@@ -29,3 +29,50 @@ pub fn split_at_to(params: &str) -> Vec<&str> {
 pub fn split_at_numop(params: &str) -> Vec<&str> {
     return params.split_terminator("#").collect::<Vec<&str>>();
 }
+
+// Split at "/" but keep the divisions of derived metric expressions: slashes inside parentheses
+// or braces and slashes between events, e.g. {a}/{b} or {a} / 2. Query parts never start with "{",
+// metrics ending in an event before the next part, e.g. ...;{a}/id, need parentheses
+pub fn split_at_slash(params: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in params.char_indices() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            '/' if depth == 0 && !is_event_division(params, i) => {
+                out.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < params.len() {
+        out.push(&params[start..]);
+    }
+    out
+}
+
+// The slash at i follows or precedes an event in braces
+fn is_event_division(params: &str, i: usize) -> bool {
+    params[..i].trim_end().ends_with('}') || params[i + 1..].trim_start().starts_with('{')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn divisions_of_metrics_are_not_split() {
+        assert_eq!(
+            split_at_slash("?operator=\"a\"/count?operator;(r={a}/{b};s=({a}+1)/2)/count"),
+            vec!["?operator=\"a\"", "count?operator;(r={a}/{b};s=({a}+1)/2)", "count"]
+        );
+        assert_eq!(
+            split_at_slash("derived?operator!x={a} / {b};y={a}/2"),
+            vec!["derived?operator!x={a} / {b};y={a}/2"]
+        );
+        assert_eq!(split_at_slash("operator/count?operator/"), vec!["operator", "count?operator"]);
+    }
+}
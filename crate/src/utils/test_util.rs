@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use arrow::{
+    array::{Float64Array, Int64Array, StringArray, UInt64Array},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+
+use crate::utils::record_batch_util::create_new_record_batch;
+
+static OPERATORS: [&str; 4] = ["tablescan1", "hashjoin2", "groupby3", "No operator"];
+static EVENTS: [&str; 2] = ["cycles", "loads"];
+static PIPELINES: [&str; 3] = ["pipeline1", "pipeline2", "pipeline3"];
+
+// Unfiltered batch of a profile with rows samples ordered by time.
+// Times advance in steps of 0.001 to 0.01 and every tenth sample sits on a multiple of 0.01,
+// the columns cycle through the values with different periods
+pub fn profile_batch(rows: usize) -> RecordBatch {
+    let mut time = 0.;
+    let mut time_vec = Vec::new();
+    let mut operator_vec = Vec::new();
+    let mut event_vec = Vec::new();
+    let mut pipeline_vec = Vec::new();
+    for i in 0..rows {
+        time += ((i * 7) % 10 + 1) as f64 / 1000.;
+        if i % 10 == 0 {
+            time = (time * 100.).ceil() / 100.;
+        }
+        time_vec.push(time);
+        operator_vec.push(OPERATORS[(i * 3 + i / 5) % OPERATORS.len()]);
+        event_vec.push(EVENTS[(i / 3) % EVENTS.len()]);
+        pipeline_vec.push(PIPELINES[(i / 7) % PIPELINES.len()]);
+    }
+
+    create_new_record_batch(
+        vec![
+            "operator",
+            "ev_name",
+            "time",
+            "pipeline",
+            "addr",
+            "uri",
+            "op_ext",
+            "physical_op",
+        ],
+        vec![
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Utf8,
+            DataType::UInt64,
+            DataType::Int64,
+            DataType::Utf8,
+            DataType::Utf8,
+        ],
+        vec![
+            Arc::new(StringArray::from(operator_vec.clone())),
+            Arc::new(StringArray::from(event_vec)),
+            Arc::new(Float64Array::from(time_vec)),
+            Arc::new(StringArray::from(pipeline_vec)),
+            Arc::new(UInt64Array::from(vec![0; rows])),
+            Arc::new(Int64Array::from(vec![0; rows])),
+            Arc::new(StringArray::from(operator_vec.clone())),
+            Arc::new(StringArray::from(operator_vec)),
+        ],
+    )
+}
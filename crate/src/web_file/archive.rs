@@ -0,0 +1,97 @@
+use std::io::{BufReader, Read};
+
+use serde::Deserialize;
+
+use super::{
+    serde_reader::{DICT_FILE_NAME, QUERY_PLAN_FILE_NAME, URI_DICT_FILE_NAME},
+    web_file_chunkreader::PARQUET_FILE_NAME,
+    zip_entry::ZipEntry,
+};
+
+pub static MANIFEST_FILE_NAME: &str = "manifest.json";
+// Name of the only query of archives without query list
+pub static DEFAULT_QUERY_NAME: &str = "query";
+
+// Query of a multi-query archive, files default to <name>/<file name of single-query archives>
+#[derive(Deserialize, Debug, Clone)]
+pub struct ManifestQuery {
+    pub name: String,
+    pub samples: Option<String>,
+    pub dictionary: Option<String>,
+    pub uir: Option<String>,
+    pub query_plan: Option<String>,
+}
+
+// Optional manifest of a profile archive
+#[derive(Deserialize, Debug, Clone)]
+pub struct Manifest {
+    pub version: u64,
+    #[serde(default)]
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub queries: Vec<ManifestQuery>,
+}
+
+// Files of one query inside the archive
+#[derive(Clone, Debug)]
+pub struct QueryFiles {
+    pub name: String,
+    pub samples: String,
+    pub dictionary: String,
+    pub uir: String,
+    pub query_plan: String,
+}
+
+impl Default for QueryFiles {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_QUERY_NAME.to_string(),
+            samples: PARQUET_FILE_NAME.to_string(),
+            dictionary: DICT_FILE_NAME.to_string(),
+            uir: URI_DICT_FILE_NAME.to_string(),
+            query_plan: QUERY_PLAN_FILE_NAME.to_string(),
+        }
+    }
+}
+
+impl QueryFiles {
+    fn from_manifest(query: &ManifestQuery) -> Self {
+        let path = |file: &Option<String>, default: &str| match file {
+            Some(file) => file.to_owned(),
+            None => format!("{}/{}", query.name, default),
+        };
+        Self {
+            name: query.name.to_owned(),
+            samples: path(&query.samples, PARQUET_FILE_NAME),
+            dictionary: path(&query.dictionary, DICT_FILE_NAME),
+            uir: path(&query.uir, URI_DICT_FILE_NAME),
+            query_plan: path(&query.query_plan, QUERY_PLAN_FILE_NAME),
+        }
+    }
+}
+
+impl Manifest {
+    // Queries of the archive, a single query in the archive root without query list
+    pub fn query_files(&self) -> Vec<QueryFiles> {
+        if self.queries.is_empty() {
+            vec![QueryFiles::default()]
+        } else {
+            self.queries.iter().map(QueryFiles::from_manifest).collect()
+        }
+    }
+}
+
+pub fn read_manifest_text(file_size: u64) -> Option<String> {
+    let reader = ZipEntry::open(MANIFEST_FILE_NAME, file_size)?;
+    let mut buf = String::new();
+    let _result = BufReader::new(reader.reader()).read_to_string(&mut buf);
+    Some(buf)
+}
+
+// Queries of the archive, errors in the manifest are reported by the validation
+pub fn read_archive_queries(file_size: u64) -> Vec<QueryFiles> {
+    read_manifest_text(file_size)
+        .and_then(|text| serde_json::from_str::<Manifest>(&text).ok())
+        .map(|manifest| manifest.query_files())
+        .unwrap_or(vec![QueryFiles::default()])
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+// Keys under which the children of an operator are stored in query_plan_analyzed.json
+static PLAN_CHILDREN: [&str; 5] = ["input", "left", "right", "magic", "temp"];
+
+#[derive(Clone, Debug)]
+pub struct PlanNode {
+    // Index in QueryPlan::nodes
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    // input, left, right, magic, temp or root
+    pub child_type: String,
+    // Operator id, same as the operator id of the samples
+    pub operator: String,
+    pub analyze_plan_id: Option<i64>,
+    // Estimated cardinality of the optimizer
+    pub cardinality: Option<f64>,
+    // Observed cardinality
+    pub analyzed_cardinality: Option<f64>,
+    // Estimated cost of the optimizer
+    pub cost: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QueryPlan {
+    // Nodes in pre-order, the root is the first node
+    pub nodes: Vec<PlanNode>,
+    // Operator id => index of the node
+    pub operator_index: HashMap<String, usize>,
+}
+
+fn as_f64(value: Option<&Value>) -> Option<f64> {
+    match value {
+        Some(Value::Number(x)) => x.as_f64(),
+        Some(Value::String(x)) => x.parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+impl QueryPlan {
+    pub fn parse(json: &str) -> Option<QueryPlan> {
+        let value = serde_json::from_str::<Value>(json).ok()?;
+        value.get("operator")?;
+
+        let mut plan = QueryPlan {
+            nodes: Vec::new(),
+            operator_index: HashMap::new(),
+        };
+        plan.add_node(&value, None, "root");
+        Some(plan)
+    }
+
+    fn add_node(&mut self, value: &Value, parent: Option<usize>, child_type: &str) {
+        let id = self.nodes.len();
+        let operator = value
+            .get("operator")
+            .and_then(|x| x.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        self.operator_index.insert(operator.to_owned(), id);
+        self.nodes.push(PlanNode {
+            id: id,
+            parent: parent,
+            children: Vec::new(),
+            child_type: child_type.to_string(),
+            operator: operator,
+            analyze_plan_id: value.get("analyzePlanId").and_then(|x| x.as_i64()),
+            cardinality: as_f64(value.get("cardinality")),
+            analyzed_cardinality: as_f64(value.get("analyzePlanCardinality")),
+            cost: as_f64(value.get("cost")),
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(id);
+        }
+
+        for child_type in PLAN_CHILDREN.iter() {
+            if let Some(child) = value.get(child_type) {
+                if child.is_object() {
+                    self.add_node(child, Some(id), child_type);
+                }
+            }
+        }
+    }
+
+    pub fn node_of_operator(&self, operator: &str) -> Option<&PlanNode> {
+        self.operator_index.get(operator).map(|id| &self.nodes[*id])
+    }
+
+    // Operator ids from the root down to the node of operator, empty if it is not in the plan
+    pub fn path_of_operator(&self, operator: &str) -> Vec<&str> {
+        let mut path = Vec::new();
+        let mut node = self.node_of_operator(operator);
+        while let Some(current) = node {
+            path.push(current.operator.as_str());
+            node = current.parent.map(|parent| &self.nodes[parent]);
+        }
+        path.reverse();
+        path
+    }
+
+    // Parent -> child operator ids
+    pub fn edges(&self) -> Vec<(&str, &str)> {
+        self.nodes
+            .iter()
+            .filter_map(|node| {
+                node.parent
+                    .map(|parent| (self.nodes[parent].operator.as_str(), node.operator.as_str()))
+            })
+            .collect()
+    }
+}
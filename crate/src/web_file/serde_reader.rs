@@ -1,15 +1,20 @@
 use std::{collections::HashMap, io::{Read, BufReader}};
-use crate::{
-    bindings::send_js_query_plan,
-};
+use crate::state::state::get_query_files;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
-use super::{parquet_reader::BufferReader, streambuf::WebFileReader};
+use super::{
+    query_plan::QueryPlan,
+    uir_program::UirProgram,
+    validation::{add_validation_issue, Severity},
+    zip_entry::ZipEntry,
+};
 use crate::{web_file::serde_reader::Value::Number};
 
-#[derive(Deserialize, Debug, Clone)]
+// Missing sections are empty, their ids are reported as unknown while mapping
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
 struct Dictionary {
     operators: Map<String, Value>,
     pipelines: Map<String, Value>,
@@ -30,15 +35,22 @@ pub struct DictionaryUri {
     pub op: Option<String>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SerdeDict {
     pub dict: HashMap<i64, HashMap<u64, String>>,
-    pub uri_dict: HashMap<String, DictionaryUri>
+    // Parsed from query_plan_json
+    #[serde(skip)]
+    pub query_plan: Option<QueryPlan>,
+    pub uir_program: UirProgram,
+    // mapping section of the dictionary
+    pub mapping: HashMap<String, String>,
+    // Raw query plan for the frontend, empty if the archive has none
+    pub query_plan_json: String,
 }
 
-static DICT_FILE_NAME: &str = "dictionary_compression.json";
-static URI_DICT_FILE_NAME: &str = "uir.json";
-static QUERY_PLAN_FILE_NAME: &str = "query_plan_analyzed.json";
+pub static DICT_FILE_NAME: &str = "dictionary_compression.json";
+pub static URI_DICT_FILE_NAME: &str = "uir.json";
+pub static QUERY_PLAN_FILE_NAME: &str = "query_plan_analyzed.json";
 
 pub enum DictFields {
     Operator = 0,
@@ -47,22 +59,55 @@ pub enum DictFields {
     Srcline = 3,
     OpExtension = 4,
     PhysicalOp = 5,
+    Dso = 6,
 }
 
 impl SerdeDict {
+    // Event and operator names of the dictionary of any query of the archive,
+    // empty if it can't be read
+    pub fn read_event_and_operator_names(
+        dictionary: &str,
+        length: u64,
+    ) -> (HashMap<u64, String>, HashMap<u64, String>) {
+        let reader = ZipEntry::open(dictionary, length);
+        let d: Dictionary = match reader.map(|r| serde_json::from_reader(BufReader::new(r.reader()))) {
+            Some(Ok(d)) => d,
+            _ => Dictionary::default(),
+        };
+        let names = |section: Map<String, Value>| {
+            section
+                .into_iter()
+                .filter_map(|(name, id)| id.as_u64().map(|id| (id, name)))
+                .collect::<HashMap<u64, String>>()
+        };
+        (names(d.events), names(d.operators))
+    }
+
+    // Reads the dictionaries of the loaded query
     pub fn read_dict(length: u64) -> Self {
+        let files = get_query_files();
 
-        let mut zip =
-            zip::ZipArchive::new(WebFileReader::new_from_file(length as i32)).unwrap();
-        let reader = zip.by_name(QUERY_PLAN_FILE_NAME).unwrap();
-        let mut buf_reader = BufReader::new(reader);
-        
+        // The archive was validated before, optional files may be missing
         let mut buf: String = String::new();
-        let _result = buf_reader.read_to_string(&mut buf);
+        if let Some(reader) = ZipEntry::open(&files.query_plan, length) {
+            let mut buf_reader = BufReader::new(reader.reader());
+            let _result = buf_reader.read_to_string(&mut buf);
+        }
 
-        let reader = BufferReader::read_to_buffer(DICT_FILE_NAME, length as u64);
-        let buf_reader = BufReader::new(reader);
-        let d: Dictionary = serde_json::from_reader(buf_reader).unwrap();
+        let reader = ZipEntry::open(&files.dictionary, length);
+        let d: Dictionary = match reader.map(|r| serde_json::from_reader(BufReader::new(r.reader()))) {
+            Some(Ok(d)) => d,
+            Some(Err(err)) => {
+                add_validation_issue(
+                    Severity::Error,
+                    &files.dictionary,
+                    format!("Invalid dictionary: {}", err),
+                    1,
+                );
+                Dictionary::default()
+            }
+            None => Dictionary::default(),
+        };
 
         let mut hash_map = HashMap::new();
         for operator in d.operators {
@@ -131,15 +176,62 @@ impl SerdeDict {
             }
         }
 
-        let reader = BufferReader::read_to_buffer(URI_DICT_FILE_NAME, length as u64);
-        let buf_reader = BufReader::new(reader);
-        let d: HashMap<String, DictionaryUri> = serde_json::from_reader(buf_reader).unwrap();
+        for dso in d.dso {
+            let inner_hash_map = hash_map
+                .entry(DictFields::Dso as i64)
+                .or_insert(HashMap::new());
+            let string = dso.0;
+            if let Number(x) = dso.1 {
+                let num = x.as_u64().unwrap();
+                inner_hash_map.insert(num, string);
+            }
+        }
+
+        let mapping = d
+            .mapping
+            .into_iter()
+            .map(|(key, value)| match value {
+                Value::String(value) => (key, value),
+                value => (key, value.to_string()),
+            })
+            .collect::<HashMap<String, String>>();
+
+        for field in DictFields::Operator as i64..=DictFields::Dso as i64 {
+            hash_map.entry(field).or_insert(HashMap::new());
+        }
+
+        let reader = ZipEntry::open(&files.uir, length);
+        let d: HashMap<String, DictionaryUri> =
+            match reader.map(|r| serde_json::from_reader(BufReader::new(r.reader()))) {
+                Some(Ok(d)) => d,
+                Some(Err(err)) => {
+                    add_validation_issue(
+                        Severity::Warning,
+                        &files.uir,
+                        format!("Invalid UIR, UIR view is unavailable: {}", err),
+                        1,
+                    );
+                    HashMap::new()
+                }
+                None => HashMap::new(),
+            };
+
+        let query_plan = QueryPlan::parse(&buf);
+        if query_plan.is_none() && !buf.is_empty() {
+            add_validation_issue(
+                Severity::Warning,
+                &files.query_plan,
+                "Invalid query plan, plan analyses are unavailable".to_string(),
+                1,
+            );
+        }
 
-        send_js_query_plan(buf);
-        
         return Self {
             dict: hash_map,
-            uri_dict: d
+            uir_program: UirProgram::parse(&d),
+            mapping: mapping,
+            query_plan: query_plan,
+            query_plan_json: buf,
         };
     }
 }
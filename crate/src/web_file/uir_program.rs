@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::serde_reader::DictionaryUri;
+
+// Opcodes which end a basic block
+static TERMINATORS: [&str; 6] = ["br", "condbr", "return", "ret", "switch", "unreachable"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum UirLineKind {
+    // define / declare
+    FunctionStart,
+    FunctionEnd,
+    // Label of a basic block
+    Label,
+    Instruction,
+    // Constants, globals and empty lines
+    Other,
+}
+
+// Classification by the first token of a line, instructions are indented
+pub fn classify_line(text: &str) -> UirLineKind {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return UirLineKind::Other;
+    }
+    if text.starts_with(char::is_whitespace) {
+        return UirLineKind::Instruction;
+    }
+    match trimmed.split_whitespace().next().unwrap() {
+        "define" | "declare" => UirLineKind::FunctionStart,
+        "}" | "]" => UirLineKind::FunctionEnd,
+        token if token.ends_with(':') => UirLineKind::Label,
+        _ => UirLineKind::Other,
+    }
+}
+
+// Line of uir.json
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UirLine {
+    // Key in uir.json, samples refer to it via the srcline dictionary
+    pub line: u64,
+    pub text: String,
+    pub kind: UirLineKind,
+    // Function the line belongs to, from its define / declare line up to the closing line
+    pub function: Option<usize>,
+    // Index in UirProgram::instructions for instructions
+    pub instruction: Option<usize>,
+    pub op: Option<String>,
+    pub pipeline: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UirInstruction {
+    // instrId of uir.json, the line number if missing
+    pub instr_id: String,
+    pub line: u64,
+    pub opcode: String,
+    pub function: usize,
+    pub block: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UirBlock {
+    pub id: usize,
+    pub label: String,
+    pub function: usize,
+    pub instructions: Vec<usize>,
+    pub successors: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UirFunction {
+    pub name: String,
+    // Line of the define / declare
+    pub line: u64,
+    pub blocks: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct UirProgram {
+    // Lines sorted by line number
+    pub lines: Vec<UirLine>,
+    pub functions: Vec<UirFunction>,
+    pub blocks: Vec<UirBlock>,
+    pub instructions: Vec<UirInstruction>,
+    // Line number => index in lines
+    pub line_index: HashMap<u64, usize>,
+}
+
+// Function name from "define int32 @name(...)", the whole line as fallback
+fn function_name(text: &str) -> String {
+    text.split_whitespace()
+        .find(|token| token.starts_with('@'))
+        .map(|token| token.split('(').next().unwrap().to_string())
+        .unwrap_or(text.trim().to_string())
+}
+
+// Opcode of "%x = opcode ..." or "opcode ..."
+fn opcode(text: &str) -> String {
+    let tokens = text.split_whitespace().collect::<Vec<&str>>();
+    let token = if tokens.len() > 2 && tokens[1] == "=" {
+        tokens[2]
+    } else {
+        tokens.first().copied().unwrap_or("")
+    };
+    token.to_string()
+}
+
+impl UirProgram {
+    pub fn parse(uri_dict: &HashMap<String, DictionaryUri>) -> UirProgram {
+        let mut entries = uri_dict
+            .iter()
+            .filter_map(|(key, entry)| key.parse::<u64>().ok().map(|line| (line, entry)))
+            .collect::<Vec<(u64, &DictionaryUri)>>();
+        entries.sort_by_key(|entry| entry.0);
+
+        let mut program = UirProgram::default();
+        let mut function: Option<usize> = None;
+        let mut block: Option<usize> = None;
+
+        for (line, entry) in entries {
+            let text = entry.uir.to_owned().unwrap_or(String::new());
+            let kind = classify_line(&text);
+            let mut instruction = None;
+            // The closing line still belongs to the function
+            let mut line_function = function;
+
+            match kind {
+                UirLineKind::FunctionStart => {
+                    // Previous function without closing line
+                    program.resolve_successors(function);
+                    let id = program.functions.len();
+                    program.functions.push(UirFunction {
+                        name: function_name(&text),
+                        line: line,
+                        blocks: Vec::new(),
+                    });
+                    function = Some(id);
+                    line_function = function;
+                    block = None;
+                }
+                UirLineKind::FunctionEnd => {
+                    program.resolve_successors(function);
+                    function = None;
+                    block = None;
+                }
+                UirLineKind::Label => {
+                    if let Some(function) = function {
+                        let label = text.trim().trim_end_matches(':').to_string();
+                        block = Some(program.add_block(function, label));
+                    }
+                }
+                UirLineKind::Instruction => {
+                    if let Some(function) = function {
+                        // Instructions before the first label form the entry block
+                        let current_block = match block {
+                            Some(block) => block,
+                            None => program.add_block(function, "entry".to_string()),
+                        };
+                        block = Some(current_block);
+
+                        let id = program.instructions.len();
+                        program.instructions.push(UirInstruction {
+                            instr_id: entry.instrid.to_owned().unwrap_or(line.to_string()),
+                            line: line,
+                            opcode: opcode(&text),
+                            function: function,
+                            block: current_block,
+                        });
+                        program.blocks[current_block].instructions.push(id);
+                        instruction = Some(id);
+                    }
+                }
+                UirLineKind::Other => {}
+            }
+
+            program.line_index.insert(line, program.lines.len());
+            program.lines.push(UirLine {
+                line: line,
+                text: text,
+                kind: kind,
+                function: line_function,
+                instruction: instruction,
+                op: entry.op.to_owned(),
+                pipeline: entry.pipeline.to_owned(),
+            });
+        }
+        program.resolve_successors(function);
+        program
+    }
+
+    fn add_block(&mut self, function: usize, label: String) -> usize {
+        let id = self.blocks.len();
+        self.blocks.push(UirBlock {
+            id: id,
+            label: label,
+            function: function,
+            instructions: Vec::new(),
+            successors: Vec::new(),
+        });
+        self.functions[function].blocks.push(id);
+        id
+    }
+
+    // Control flow edges of a function: labels referenced by the terminator,
+    // fall through to the next block without terminator
+    fn resolve_successors(&mut self, function: Option<usize>) {
+        let function = match function {
+            Some(function) => function,
+            None => return,
+        };
+        let blocks = self.functions[function].blocks.to_owned();
+        let labels = blocks
+            .iter()
+            .map(|block| (self.blocks[*block].label.to_owned(), *block))
+            .collect::<HashMap<String, usize>>();
+
+        for (i, block) in blocks.iter().enumerate() {
+            let last = match self.blocks[*block].instructions.last() {
+                Some(last) => &self.instructions[*last],
+                None => continue,
+            };
+            let mut successors = Vec::new();
+            if TERMINATORS.contains(&last.opcode.as_str()) {
+                let text = &self.lines[self.line_index[&last.line]].text;
+                for token in text.split(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')') {
+                    let label = token.trim_start_matches('%');
+                    if let Some(target) = labels.get(label) {
+                        if !successors.contains(target) {
+                            successors.push(*target);
+                        }
+                    }
+                }
+            } else if let Some(next) = blocks.get(i + 1) {
+                successors.push(*next);
+            }
+            self.blocks[*block].successors = successors;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri_dict(lines: &[&str]) -> HashMap<String, DictionaryUri> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let entry = DictionaryUri {
+                    pipeline: Some("pipeline1".to_string()),
+                    uir: Some(text.to_string()),
+                    instrid: None,
+                    op: None,
+                };
+                ((i + 1).to_string(), entry)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lines_are_classified_by_first_token() {
+        assert_eq!(classify_line("define int32 @f()"), UirLineKind::FunctionStart);
+        assert_eq!(classify_line("}"), UirLineKind::FunctionEnd);
+        assert_eq!(classify_line("loop:"), UirLineKind::Label);
+        assert_eq!(classify_line("  %x = add int32 %a, 1"), UirLineKind::Instruction);
+        assert_eq!(classify_line("const %c = 1"), UirLineKind::Other);
+        assert_eq!(classify_line("   "), UirLineKind::Other);
+    }
+
+    #[test]
+    fn functions_blocks_and_edges_are_parsed() {
+        let program = UirProgram::parse(&uri_dict(&[
+            "define int32 @scan(int64 %n) [",
+            "  %i = const int64 0",
+            "loop:",
+            "  %c = cmpult bool %i, %n",
+            "  condbr %c %body %exit",
+            "body:",
+            "  %j = add int64 %i, 1",
+            "exit:",
+            "  return int32 0",
+            "]",
+            "define int32 @next() [",
+            "  return int32 1",
+        ]));
+
+        assert_eq!(program.functions.len(), 2);
+        assert_eq!(program.functions[0].name, "@scan");
+        assert_eq!(program.functions[0].blocks.len(), 4);
+        assert_eq!(program.lines[9].function, Some(0));
+        assert_eq!(program.lines[10].function, Some(1));
+
+        let label = |block: usize| program.blocks[block].label.as_str();
+        let successors = |block: usize| {
+            program.blocks[block]
+                .successors
+                .iter()
+                .map(|block| label(*block))
+                .collect::<Vec<&str>>()
+        };
+        assert_eq!(label(0), "entry");
+        // Fall through without terminator
+        assert_eq!(successors(0), vec!["loop"]);
+        assert_eq!(successors(1), vec!["body", "exit"]);
+        assert_eq!(successors(2), vec!["exit"]);
+        assert!(successors(3).is_empty());
+
+        let condbr = &program.instructions[program.lines[4].instruction.unwrap()];
+        assert_eq!(condbr.opcode, "condbr");
+        assert_eq!(condbr.instr_id, "5");
+        assert_eq!(program.instructions[1].opcode, "cmpult");
+    }
+}
@@ -0,0 +1,242 @@
+use std::sync::Arc;
+
+use arrow::{
+    array::{Float64Array, StringArray},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+use serde::{Deserialize, Serialize};
+use crate::{
+    state::state::get_validation_report,
+    utils::record_batch_util::create_new_record_batch,
+};
+
+use super::{
+    archive::{read_manifest_text, Manifest, QueryFiles, MANIFEST_FILE_NAME},
+    streambuf::WebFileReader,
+    zip_entry::ZipEntry,
+};
+
+// Newest archive layout this reader understands
+pub static SUPPORTED_ARCHIVE_VERSION: u64 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Severity {
+    // Profile can't be loaded
+    Error,
+    // Component is unavailable or incomplete, the profile is loaded anyway
+    Warning,
+    Info,
+}
+
+impl Severity {
+    fn as_str(&self) -> &str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    // File or dictionary the issue belongs to
+    pub component: String,
+    pub message: String,
+    // Number of occurrences, e.g. samples with unknown dictionary ids
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub version: Option<u64>,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn add(&mut self, severity: Severity, component: &str, message: String, count: u64) {
+        self.issues.push(ValidationIssue {
+            severity: severity,
+            component: component.to_string(),
+            message: message,
+            count: count,
+        });
+    }
+
+    // A profile is loadable if no errors were found
+    pub fn is_valid(&self) -> bool {
+        !self
+            .issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
+    }
+}
+
+// Checks the archive layout before anything is loaded:
+// manifest version, required files (samples, dictionary) and optional files (uir, query plan)
+pub fn validate_archive(file_size: u64) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let mut zip = match zip::ZipArchive::new(WebFileReader::new_from_file(file_size as i32)) {
+        Ok(zip) => zip,
+        Err(err) => {
+            report.add(Severity::Error, "archive", format!("Not a zip archive: {}", err), 1);
+            return report;
+        }
+    };
+    let file_names = zip.file_names().map(|x| x.to_string()).collect::<Vec<String>>();
+
+    // Manifest
+    let manifest = match read_manifest_text(file_size) {
+        Some(text) => match serde_json::from_str::<Manifest>(&text) {
+            Ok(manifest) => Some(manifest),
+            Err(err) => {
+                report.add(
+                    Severity::Error,
+                    MANIFEST_FILE_NAME,
+                    format!("Invalid manifest: {}", err),
+                    1,
+                );
+                None
+            }
+        },
+        None => {
+            report.add(
+                Severity::Info,
+                MANIFEST_FILE_NAME,
+                format!("No manifest, assuming version {}", SUPPORTED_ARCHIVE_VERSION),
+                1,
+            );
+            None
+        }
+    };
+    let queries = manifest
+        .as_ref()
+        .map(|manifest| manifest.query_files())
+        .unwrap_or(vec![QueryFiles::default()]);
+
+    if let Some(manifest) = manifest {
+        report.version = Some(manifest.version);
+        report.add(
+            Severity::Info,
+            MANIFEST_FILE_NAME,
+            format!("Archive version {}", manifest.version),
+            1,
+        );
+        if manifest.version > SUPPORTED_ARCHIVE_VERSION {
+            report.add(
+                Severity::Error,
+                MANIFEST_FILE_NAME,
+                format!(
+                    "Archive version {} is newer than the supported version {}",
+                    manifest.version, SUPPORTED_ARCHIVE_VERSION
+                ),
+                1,
+            );
+        }
+        if !manifest.queries.is_empty() {
+            report.add(
+                Severity::Info,
+                MANIFEST_FILE_NAME,
+                format!("Archive contains {} queries", manifest.queries.len()),
+                1,
+            );
+        }
+        for file in manifest.files {
+            if !file_names.contains(&file) {
+                report.add(
+                    Severity::Error,
+                    &file,
+                    "File listed in manifest is missing".to_string(),
+                    1,
+                );
+            }
+        }
+    } else {
+        report.version = Some(SUPPORTED_ARCHIVE_VERSION);
+    }
+
+    // Required files
+    for query in &queries {
+        for file in [&query.samples, &query.dictionary].iter() {
+            if !file_names.contains(file) {
+                report.add(Severity::Error, file, "Required file is missing".to_string(), 1);
+            }
+        }
+    }
+
+    // Entries with other compression methods can't be read and would appear to be missing
+    for i in 0..zip.len() {
+        if let Ok(entry) = zip.by_index_raw(i) {
+            if !ZipEntry::supported_compression(entry.compression()) {
+                let required = queries
+                    .iter()
+                    .any(|query| entry.name() == query.samples || entry.name() == query.dictionary);
+                report.add(
+                    if required { Severity::Error } else { Severity::Warning },
+                    entry.name(),
+                    format!("Unsupported compression method {}", entry.compression()),
+                    1,
+                );
+            }
+        }
+    }
+
+    // Optional files
+    for query in &queries {
+        for (file, component) in [(&query.uir, "UIR view"), (&query.query_plan, "query plan")].iter() {
+            if !file_names.contains(file) {
+                report.add(
+                    Severity::Warning,
+                    file,
+                    format!("Optional file is missing, {} is unavailable", component),
+                    1,
+                );
+            }
+        }
+    }
+
+    report
+}
+
+pub fn add_validation_issue(severity: Severity, component: &str, message: String, count: u64) {
+    let report = get_validation_report();
+    let mut report = report.lock().unwrap();
+    report.add(severity, component, message, count);
+}
+
+// Validation report as record batch
+pub fn validation_report() -> RecordBatch {
+    let report = get_validation_report();
+    let report = report.lock().unwrap();
+
+    let mut severity_vec = Vec::new();
+    let mut component_vec = Vec::new();
+    let mut message_vec = Vec::new();
+    let mut count_vec = Vec::new();
+
+    for issue in &report.issues {
+        severity_vec.push(issue.severity.as_str());
+        component_vec.push(issue.component.as_str());
+        message_vec.push(issue.message.as_str());
+        count_vec.push(issue.count as f64);
+    }
+
+    create_new_record_batch(
+        vec!["severity", "component", "message", "count"],
+        vec![
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Utf8,
+            DataType::Float64,
+        ],
+        vec![
+            Arc::new(StringArray::from(severity_vec)),
+            Arc::new(StringArray::from(component_vec)),
+            Arc::new(StringArray::from(message_vec)),
+            Arc::new(Float64Array::from(count_vec)),
+        ],
+    )
+}
@@ -2,38 +2,40 @@ use parquet::errors::Result;
 use parquet::file::reader::ChunkReader;
 use parquet::file::reader::Length;
 
-use super::parquet_reader::BufferReader;
-use super::streambuf::WebFileReader;
+use crate::state::state::get_query_files;
+
+use super::zip_entry::{EntryBytes, EntryReader, ZipEntry};
 
 pub struct WebFileChunkReader {
-    length: u64,
+    // Samples file of the loaded query, empty if it is missing
+    entry: ZipEntry,
 }
 
-static PARQUET_FILE_NAME: &str = "samples.parquet";
+pub static PARQUET_FILE_NAME: &str = "samples.parquet";
 
 impl WebFileChunkReader {
     pub fn new(file_size: i32) -> Self {
-        BufferReader::read_to_buffer(PARQUET_FILE_NAME, file_size as u64);
-        Self {
-            length: file_size as u64,
-        }
+        Self::of_file(&get_query_files().samples, file_size as u64)
+    }
+
+    // Samples file of any query of the archive
+    pub fn of_file(file_name: &str, file_size: u64) -> Self {
+        let entry = ZipEntry::open(file_name, file_size)
+            .unwrap_or(ZipEntry::Buffered(EntryBytes::empty()));
+        Self { entry: entry }
     }
 }
 
 impl ChunkReader for WebFileChunkReader {
-    type T = BufferReader;
+    type T = EntryReader;
 
-    fn get_read(&self, start: u64, _length: usize) -> Result<BufferReader> {
-        let buf_read = BufferReader::set_offset(start);
-        Ok(buf_read)
+    fn get_read(&self, start: u64, length: usize) -> Result<EntryReader> {
+        self.entry.get_read(start, length)
     }
 }
 
 impl Length for WebFileChunkReader {
     fn len(&self) -> u64 {
-        let mut zip =
-            zip::ZipArchive::new(WebFileReader::new_from_file(self.length as i32)).unwrap();
-        let reader = zip.by_name(PARQUET_FILE_NAME).unwrap();
-        reader.size()
+        self.entry.len()
     }
 }
@@ -0,0 +1,237 @@
+use std::{
+    io::{Read, Take},
+    sync::Arc,
+};
+
+use parquet::errors::Result;
+use parquet::file::reader::{ChunkReader, Length};
+use zip::{result::ZipError, CompressionMethod, ZipArchive};
+
+use crate::state::state::{get_entry_buffer, insert_entry_buffer};
+
+use super::streambuf::WebFileReader;
+
+// Range of a shared buffer, cloning and slicing don't copy the data
+#[derive(Clone)]
+pub struct EntryBytes {
+    data: Arc<Vec<u8>>,
+    start: usize,
+    end: usize,
+}
+
+impl EntryBytes {
+    pub fn new(data: Arc<Vec<u8>>) -> Self {
+        let end = data.len();
+        Self {
+            data: data,
+            start: 0,
+            end: end,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(Arc::new(Vec::new()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    // Range relative to this range, clamped to its end
+    pub fn slice(&self, start: usize, length: usize) -> Self {
+        let start = (self.start + start).min(self.end);
+        Self {
+            data: self.data.clone(),
+            start: start,
+            end: (start + length).min(self.end),
+        }
+    }
+}
+
+impl Read for EntryBytes {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read_size = buf.len().min(self.len());
+        buf[..read_size].copy_from_slice(&self.data[self.start..self.start + read_size]);
+        self.start += read_size;
+        Ok(read_size)
+    }
+}
+
+pub enum EntryReader {
+    Buffered(EntryBytes),
+    Stored(Take<WebFileReader>),
+}
+
+impl Read for EntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            EntryReader::Buffered(bytes) => bytes.read(buf),
+            EntryReader::Stored(reader) => reader.read(buf),
+        }
+    }
+}
+
+// File inside the profile archive.
+// Stored (uncompressed) entries are read by offset straight from the archive,
+// compressed entries are decompressed once into a buffer kept per file name.
+#[derive(Clone)]
+pub enum ZipEntry {
+    Buffered(EntryBytes),
+    Stored {
+        file_size: u64,
+        data_start: u64,
+        length: u64,
+    },
+}
+
+impl ZipEntry {
+    // None if the file is not part of the archive
+    pub fn open(file_name: &str, file_size: u64) -> Option<Self> {
+        if let Some(buffer) = get_entry_buffer(file_name) {
+            return Some(ZipEntry::Buffered(EntryBytes::new(buffer)));
+        }
+
+        let mut zip = zip::ZipArchive::new(WebFileReader::new_from_file(file_size as i32)).ok()?;
+        let decompressed = match zip.by_name(file_name) {
+            Ok(mut reader) => {
+                if reader.compression() == CompressionMethod::Stored {
+                    return Some(ZipEntry::Stored {
+                        file_size: file_size,
+                        data_start: reader.data_start(),
+                        length: reader.size(),
+                    });
+                }
+                let mut buffer = Vec::with_capacity(reader.size() as usize);
+                reader.read_to_end(&mut buffer).ok()?;
+                Some(buffer)
+            }
+            Err(ZipError::UnsupportedArchive(_)) => None,
+            Err(_) => return None,
+        };
+        let buffer = match decompressed {
+            Some(buffer) => buffer,
+            None => Self::decompress_raw(&mut zip, file_name)?,
+        };
+
+        let buffer = Arc::new(buffer);
+        insert_entry_buffer(file_name, buffer.clone());
+        Some(ZipEntry::Buffered(EntryBytes::new(buffer)))
+    }
+
+    // Methods entries can be decompressed with, the validation reports all others
+    pub fn supported_compression(compression: CompressionMethod) -> bool {
+        [
+            CompressionMethod::Stored,
+            CompressionMethod::DEFLATE,
+            CompressionMethod::BZIP2,
+            CompressionMethod::ZSTD,
+            CompressionMethod::ZSTD_DEPRECATED,
+        ]
+        .contains(&compression)
+    }
+
+    // Entries compressed with methods the zip crate can't decode in wasm32 (zstd, bzip2)
+    fn decompress_raw(zip: &mut ZipArchive<WebFileReader>, file_name: &str) -> Option<Vec<u8>> {
+        let index = (0..zip.len()).find(|i| {
+            zip.by_index_raw(*i)
+                .map(|entry| entry.name() == file_name)
+                .unwrap_or(false)
+        })?;
+        let mut entry = zip.by_index_raw(index).ok()?;
+        let compression = entry.compression();
+        let mut compressed = Vec::with_capacity(entry.compressed_size() as usize);
+        entry.read_to_end(&mut compressed).ok()?;
+        decompress(compression, &compressed, entry.size() as usize)
+    }
+
+    pub fn reader(&self) -> EntryReader {
+        self.slice(0, self.len() as usize)
+    }
+
+    fn slice(&self, start: u64, length: usize) -> EntryReader {
+        match self {
+            ZipEntry::Buffered(bytes) => EntryReader::Buffered(bytes.slice(start as usize, length)),
+            ZipEntry::Stored {
+                file_size,
+                data_start,
+                length: entry_length,
+            } => {
+                let start = start.min(*entry_length);
+                let length = (length as u64).min(entry_length - start);
+                let reader = WebFileReader::new_from_file(*file_size as i32)
+                    .set_offset((data_start + start) as i32);
+                EntryReader::Stored(reader.take(length))
+            }
+        }
+    }
+}
+
+impl Length for ZipEntry {
+    fn len(&self) -> u64 {
+        match self {
+            ZipEntry::Buffered(bytes) => bytes.len() as u64,
+            ZipEntry::Stored { length, .. } => *length,
+        }
+    }
+}
+
+impl ChunkReader for ZipEntry {
+    type T = EntryReader;
+
+    fn get_read(&self, start: u64, length: usize) -> Result<EntryReader> {
+        Ok(self.slice(start, length))
+    }
+}
+
+fn decompress(compression: CompressionMethod, compressed: &[u8], size: usize) -> Option<Vec<u8>> {
+    let mut buffer = Vec::with_capacity(size);
+    // Method 20 is the zstd id of older archivers, the data is the same
+    if compression == CompressionMethod::ZSTD || compression == CompressionMethod::ZSTD_DEPRECATED {
+        let mut decoder = ruzstd::StreamingDecoder::new(compressed).ok()?;
+        decoder.read_to_end(&mut buffer).ok()?;
+    } else if compression == CompressionMethod::BZIP2 {
+        let mut decoder = bzip2_rs::DecoderReader::new(compressed);
+        decoder.read_to_end(&mut buffer).ok()?;
+    } else {
+        return None;
+    }
+    Some(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Written by the zstd and bzip2 command line tools
+    const ZSTD: [u8; 34] = [
+        0x28, 0xb5, 0x2f, 0xfd, 0x24, 0x2c, 0xad, 0x00, 0x00, 0x78, 0x75, 0x6d, 0x62, 0x72, 0x61, 0x20, 0x70,
+        0x72, 0x6f, 0x66, 0x69, 0x6c, 0x65, 0x72, 0x20, 0x01, 0x00, 0x72, 0xcf, 0x3a, 0x37, 0xb2, 0xfe, 0x77,
+    ];
+    const BZIP2: [u8; 59] = [
+        0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0xb0, 0xfe, 0x83, 0x51, 0x00, 0x00, 0x14,
+        0x91, 0x80, 0x40, 0x00, 0x33, 0x26, 0xd2, 0x00, 0x20, 0x00, 0x22, 0x3d, 0x50, 0xd0, 0xf4, 0xd0, 0x40,
+        0xd0, 0x34, 0x34, 0x32, 0x10, 0x8a, 0x11, 0x45, 0x9e, 0x10, 0xe1, 0x96, 0x6e, 0x66, 0x30, 0x5d, 0xc9,
+        0x14, 0xe1, 0x42, 0x42, 0xc3, 0xfa, 0x0d, 0x44,
+    ];
+    const EXPECTED: &[u8] = b"umbra profiler umbra profiler umbra profiler";
+
+    #[test]
+    fn compressed_entries_are_decompressed() {
+        let cases: [(CompressionMethod, &[u8], Option<&[u8]>); 6] = [
+            (CompressionMethod::ZSTD, &ZSTD, Some(EXPECTED)),
+            (CompressionMethod::ZSTD_DEPRECATED, &ZSTD, Some(EXPECTED)),
+            (CompressionMethod::BZIP2, &BZIP2, Some(EXPECTED)),
+            (CompressionMethod::ZSTD, &BZIP2, None),
+            (CompressionMethod::BZIP2, &ZSTD, None),
+            (CompressionMethod::Deflated, &ZSTD, None),
+        ];
+        for (compression, compressed, expected) in cases.iter() {
+            assert_eq!(
+                decompress(*compression, compressed, EXPECTED.len()).as_deref(),
+                *expected,
+                "{:?}",
+                compression
+            );
+        }
+    }
+}
@@ -16,6 +16,29 @@ export function createResultObject(request: number, resultTable: ArrowTable.Tabl
     };
 }
 
+export interface ISubQueryResult {
+    query: string;
+    table: ArrowTable.Table<any>;
+}
+
+// Results of a "&&" query with sub-results of different length arrive as one table
+// with a row per sub-query (query_index, query, result as Arrow IPC stream)
+export function unpackSubQueryResults(resultTable: ArrowTable.Table<any>): Array<ISubQueryResult> {
+    const queries = resultTable.getColumn('query');
+    const results = resultTable.getColumn('result');
+    if (!queries || !results) {
+        return [{ query: '', table: resultTable }];
+    }
+    const subQueryResults: Array<ISubQueryResult> = [];
+    for (let i = 0; i < resultTable.length; i++) {
+        subQueryResults.push({
+            query: queries.get(i),
+            table: ArrowTable.Table.from(results.get(i)),
+        });
+    }
+    return subQueryResults;
+}
+
 export interface IKpiData {
     id: string,
     title: string,
@@ -10,7 +10,9 @@ import * as JSZip from '../node_modules/jszip/';
 export enum WorkerResponseType {
   UMBRAPERF_FILE_READING_FINISHED = 'UMBRAPERF_FILE_READING_FINISHED',
   STORE_RESULT = 'STORE_RESULT',
+  STORE_RESULT_CHUNK = 'STORE_RESULT_CHUNK',
   STORE_QUERYPLAN_JSON = 'STORE_QUERYPLAN_JSON',
+  STORE_SESSION = 'STORE_SESSION',
 };
 
 export type WorkerResponse<T, P> = {
@@ -26,6 +28,17 @@ export interface IStoreResultResponseData {
   metaRequest: boolean,
 }
 
+// Part of a streamed result: the schema (sequence -1), a slice (sequence 0..n-1)
+// or the end marker (end, sequence n)
+export interface IStoreResultChunkResponseData {
+  requestId: number,
+  sequence: number,
+  chunk: Uint8Array | undefined,
+  end: boolean,
+  backendQueryType: BackendApi.BackendQueryType,
+  metaRequest: boolean,
+}
+
 export interface IStoreQueryplanResponseData {
   queryPlanData: object,
 }
@@ -33,7 +46,9 @@ export interface IStoreQueryplanResponseData {
 export type WorkerResponseVariant =
   WorkerResponse<WorkerResponseType.UMBRAPERF_FILE_READING_FINISHED, number> |
   WorkerResponse<WorkerResponseType.STORE_RESULT, IStoreResultResponseData> |
-  WorkerResponse<WorkerResponseType.STORE_QUERYPLAN_JSON, IStoreQueryplanResponseData>
+  WorkerResponse<WorkerResponseType.STORE_RESULT_CHUNK, IStoreResultChunkResponseData> |
+  WorkerResponse<WorkerResponseType.STORE_QUERYPLAN_JSON, IStoreQueryplanResponseData> |
+  WorkerResponse<WorkerResponseType.STORE_SESSION, Uint8Array>
   ;
 
 
@@ -42,6 +57,10 @@ export type WorkerResponseVariant =
 export enum WorkerRequestType {
   REGISTER_FILE = 'REGISTER_FILE',
   CALCULATE_CHART_DATA = 'CALCULATE_CHART_DATA',
+  SELECT_QUERY = 'SELECT_QUERY',
+  SET_OPERATOR_MAPPING = 'SET_OPERATOR_MAPPING',
+  EXPORT_SESSION = 'EXPORT_SESSION',
+  IMPORT_SESSION = 'IMPORT_SESSION',
   TEST = 'TEST',
 };
 
@@ -60,7 +79,11 @@ export interface ICalculateChartDataRequestData {
 
 export type WorkerRequestVariant =
   WorkerRequest<WorkerRequestType.REGISTER_FILE, File> |
-  WorkerRequest<WorkerRequestType.CALCULATE_CHART_DATA, ICalculateChartDataRequestData>
+  WorkerRequest<WorkerRequestType.CALCULATE_CHART_DATA, ICalculateChartDataRequestData> |
+  WorkerRequest<WorkerRequestType.SELECT_QUERY, string> |
+  WorkerRequest<WorkerRequestType.SET_OPERATOR_MAPPING, string> |
+  WorkerRequest<WorkerRequestType.EXPORT_SESSION, boolean> |
+  WorkerRequest<WorkerRequestType.IMPORT_SESSION, Uint8Array>
   ;
 
 
@@ -145,6 +168,35 @@ export function sendJsQueryResult(result: any) {
 
 }
 
+function postResultChunk(sequence: number, chunk: Uint8Array | undefined, end: boolean) {
+  const data: IStoreResultChunkResponseData = {
+    requestId: globalRequestId!,
+    sequence: sequence,
+    chunk: chunk,
+    end: end,
+    backendQueryType: globalBackendQueryType!,
+    metaRequest: globalMetaRequest,
+  };
+  // The chunk is moved to the main thread instead of copied
+  (worker as any).postMessage({
+    messageId: 201,
+    type: WorkerResponseType.STORE_RESULT_CHUNK,
+    data: data,
+  }, chunk ? [chunk.buffer] : []);
+}
+
+export function sendJsResultSchema(schema: Uint8Array) {
+  postResultChunk(-1, schema, false);
+}
+
+export function sendJsResultChunk(sequence: number, chunk: Uint8Array) {
+  postResultChunk(sequence, chunk, false);
+}
+
+export function sendJsResultEnd(chunks: number) {
+  postResultChunk(chunks, undefined, true);
+}
+
 // Receive from the main thread
 worker.onmessage = (message) => {
 
@@ -169,6 +221,26 @@ worker.onmessage = (message) => {
       profiler_core.requestChartData((messageData as ICalculateChartDataRequestData).backendQuery);
       break;
 
+    case WorkerRequestType.SELECT_QUERY:
+      profiler_core.selectQuery(messageData as string);
+      break;
+
+    case WorkerRequestType.SET_OPERATOR_MAPPING:
+      profiler_core.setOperatorMapping(messageData as string);
+      break;
+
+    case WorkerRequestType.EXPORT_SESSION:
+      worker.postMessage({
+        messageId: 201,
+        type: WorkerResponseType.STORE_SESSION,
+        data: profiler_core.exportSession(messageData as boolean),
+      });
+      break;
+
+    case WorkerRequestType.IMPORT_SESSION:
+      profiler_core.importSession(messageData as Uint8Array);
+      break;
+
     default:
   }
 
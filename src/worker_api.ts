@@ -8,6 +8,23 @@ import * as BackendApi from './model/backend_queries';
 
 const worker = new Worker(new URL('./worker.ts', import.meta.url));
 
+// Schema and table of the slices received so far of streamed results per request
+let streamedResults: { [requestId: number]: { schema: Uint8Array, table: ArrowTable.Table<any> | undefined } } = {};
+
+function concatChunks(chunks: Array<Uint8Array>) {
+    const length = chunks.reduce((sum, chunk) => sum + chunk.length, 0);
+    const result = new Uint8Array(length);
+    let offset = 0;
+    for (const chunk of chunks) {
+        result.set(chunk, offset);
+        offset += chunk.length;
+    }
+    return result;
+}
+
+// Resolves the pending session export
+let sessionExportResolver: ((session: Uint8Array) => void) | undefined = undefined;
+
 export class WorkerAPI {
     worker!: Worker;
 
@@ -24,6 +41,41 @@ export class WorkerAPI {
         });
     }
 
+    // Loads another query of a multi-query archive
+    public selectQuery(queryName: string) {
+        this.worker.postMessage({
+            type: model.WorkerRequestType.SELECT_QUERY,
+            data: queryName
+        });
+    }
+
+    // Operator symbols as JSON object, operator kind => symbol
+    public setOperatorMapping(mapping: string) {
+        this.worker.postMessage({
+            type: model.WorkerRequestType.SET_OPERATOR_MAPPING,
+            data: mapping
+        });
+    }
+
+    // Loaded profile as bytes, e.g. to be kept in IndexedDB
+    public exportSession(includeCaches: boolean): Promise<Uint8Array> {
+        return new Promise((resolve) => {
+            sessionExportResolver = resolve;
+            this.worker.postMessage({
+                type: model.WorkerRequestType.EXPORT_SESSION,
+                data: includeCaches
+            });
+        });
+    }
+
+    // Restores an exported session, finishes like registerFile
+    public importSession(session: Uint8Array) {
+        this.worker.postMessage({
+            type: model.WorkerRequestType.IMPORT_SESSION,
+            data: session
+        });
+    }
+
     public calculateChartData(backendQuery: string, requestId: number, metaRequest: boolean, backendQueryType: BackendApi.BackendQueryType) {
         console.log("REQ: " + backendQueryType + ", " + requestId);
         const requestData: ICalculateChartDataRequestData = {
@@ -65,10 +117,40 @@ worker.addEventListener('message', message => {
             Controller.storeResultFromRust(resultRequestId, resultArrowTable, metaRequest, resultBackendQueryType);
             break;
 
+        case model.WorkerResponseType.STORE_RESULT_CHUNK: {
+            const chunkRequestId = messageData.requestId;
+            if (messageData.sequence < 0) {
+                streamedResults[chunkRequestId] = { schema: messageData.chunk, table: undefined };
+                break;
+            }
+            const streamed = streamedResults[chunkRequestId];
+            if (!streamed) break;
+            // Every slice was rendered on arrival
+            if (messageData.end) {
+                delete streamedResults[chunkRequestId];
+                break;
+            }
+            if (messageData.chunk) {
+                console.log("RESP: " + messageData.backendQueryType + ", " + chunkRequestId + ", chunk " + messageData.sequence);
+                // Only the new slice is read, the table keeps the record batches of the previous ones
+                const slice = ArrowTable.Table.from(concatChunks([streamed.schema, messageData.chunk]));
+                streamed.table = streamed.table ? streamed.table.concat(slice) : slice;
+                Controller.storeResultFromRust(chunkRequestId, streamed.table, messageData.metaRequest, messageData.backendQueryType);
+            }
+            break;
+        }
+
         case model.WorkerResponseType.STORE_QUERYPLAN_JSON:
             Controller.setQueryPlanJson(messageData.queryPlanData);
             break;
 
+        case model.WorkerResponseType.STORE_SESSION:
+            if (sessionExportResolver) {
+                sessionExportResolver(messageData);
+                sessionExportResolver = undefined;
+            }
+            break;
+
         default:
             console.log("Unknown message type from worker.");
 